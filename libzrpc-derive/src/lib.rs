@@ -0,0 +1,173 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, ItemImpl, LitStr, parse_macro_input};
+
+mod service;
+
+/// Converts a struct with named fields into a `ZRpcDt::Map` keyed by field
+/// name, implementing `IntoZRpcDt` and `ZRpcDtAuto`. A field can be given a
+/// different key with `#[zrpc(rename = "...")]`, and `None` fields are sent
+/// as `ZRpcDt::Null`.
+///
+/// For a struct that also implements `Serialize`, the `ZRpcDtAuto` impl
+/// specializes the bincode one, which needs `#![feature(specialization)]` in
+/// the deriving crate.
+#[proc_macro_derive(ZRpcSerialize, attributes(zrpc))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_serialize(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Converts a `ZRpcDt::Map` back into a struct with named fields,
+/// implementing `FromZRpcDt`. Missing keys are read as `ZRpcDt::Null`, so
+/// `Option` fields become `None`, while any other field fails with an error
+/// naming it.
+#[proc_macro_derive(ZRpcDeserialize, attributes(zrpc))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_deserialize(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Turns the `&self` methods of an impl block into the procedures of a
+/// `ZRpcService`, by implementing `From<Self> for ZRpcService`. Each method
+/// is registered under its name, or under `#[zrpc(rename = "...")]`, with
+/// its parameters converted through `FromZRpcDt` and its result through
+/// `IntoProcResult`. A method taking a `ZRpcContext` first receives the
+/// call's context, and `#[zrpc(skip)]` leaves a method out.
+#[proc_macro_attribute]
+pub fn zrpc_service(attr: TokenStream, input: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "zrpc_service takes no arguments",
+        )
+        .into_compile_error()
+        .into();
+    }
+
+    let input = parse_macro_input!(input as ItemImpl);
+
+    service::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct Field<'a> {
+    ident: &'a Ident,
+    key: String,
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field<'_>>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(unsupported(input)),
+        },
+        _ => return Err(unsupported(input)),
+    };
+
+    fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().expect("named field");
+            let mut key = ident.to_string();
+
+            for attr in field.attrs.iter().filter(|a| a.path().is_ident("zrpc")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        key = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported zrpc attribute, expected `rename`"))
+                    }
+                })?;
+            }
+
+            Ok(Field { ident, key })
+        })
+        .collect()
+}
+
+fn unsupported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(
+        &input.ident,
+        "ZRpc conversions can only be derived for structs with named fields",
+    )
+}
+
+fn expand_serialize(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let fields = fields(input)?;
+
+    let (keys, idents): (Vec<_>, Vec<_>) = fields.iter().map(|f| (&f.key, f.ident)).unzip();
+
+    Ok(quote! {
+        impl #impl_generics ::libzrpc::types::dt::IntoZRpcDt for #name #ty_generics #where_clause {
+            fn into_zdt(self) -> ::libzrpc::types::dt::ZRpcDt {
+                let mut map = ::std::collections::HashMap::new();
+                #(
+                    map.insert(
+                        #keys.to_string(),
+                        ::libzrpc::types::dt::IntoZRpcDt::into_zdt(self.#idents),
+                    );
+                )*
+                ::libzrpc::types::dt::ZRpcDt::Map(map)
+            }
+        }
+
+        impl #impl_generics ::libzrpc::types::dt::ZRpcDtAuto for #name #ty_generics #where_clause {
+            fn to_zdt(&self) -> ::libzrpc::types::dt::ZRpcDt {
+                let mut map = ::std::collections::HashMap::new();
+                #(
+                    map.insert(
+                        #keys.to_string(),
+                        ::libzrpc::types::dt::ZRpcDtAuto::to_zdt(&self.#idents),
+                    );
+                )*
+                ::libzrpc::types::dt::ZRpcDt::Map(map)
+            }
+        }
+    })
+}
+
+fn expand_deserialize(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let fields = fields(input)?;
+
+    let (keys, idents): (Vec<_>, Vec<_>) = fields.iter().map(|f| (&f.key, f.ident)).unzip();
+
+    Ok(quote! {
+        impl #impl_generics ::libzrpc::types::dt::FromZRpcDt for #name #ty_generics #where_clause {
+            fn from_zdt(
+                dt: &::libzrpc::types::dt::ZRpcDt,
+            ) -> ::std::result::Result<Self, ::libzrpc::types::dt::ZRpcDtError> {
+                use ::libzrpc::types::dt::{FromZRpcDt, ZRpcDt, ZRpcDtError};
+
+                let ZRpcDt::Map(map) = dt else {
+                    return Err(ZRpcDtError::mismatch(stringify!(#name), dt));
+                };
+
+                Ok(Self {
+                    #(
+                        #idents: match map.get(#keys) {
+                            Some(v) => FromZRpcDt::from_zdt(v)
+                                .map_err(|e| ZRpcDtError(format!("{}: {}", #keys, e)))?,
+                            None => FromZRpcDt::from_zdt(&ZRpcDt::Null).map_err(|_| {
+                                ZRpcDtError(format!("missing field `{}`", #keys))
+                            })?,
+                        },
+                    )*
+                })
+            }
+        }
+    })
+}
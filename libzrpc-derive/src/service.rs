@@ -0,0 +1,153 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{FnArg, ImplItem, ImplItemFn, ItemImpl, LitStr, ReceiverKind, Type};
+
+#[derive(Default)]
+struct Options {
+    skip: bool,
+    rename: Option<String>,
+}
+
+pub(crate) fn expand(mut item: ItemImpl) -> syn::Result<TokenStream2> {
+    let mut registrations = Vec::new();
+
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(method) = impl_item else {
+            continue;
+        };
+
+        let options = take_options(method)?;
+
+        // Constructors and other associated functions aren't procedures.
+        let Some(receiver) = method.sig.receiver() else {
+            continue;
+        };
+
+        if options.skip {
+            continue;
+        }
+
+        if !matches!(receiver.kind, ReceiverKind::Reference(_, _, None)) {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "zrpc_service methods must take `&self`",
+            ));
+        }
+
+        registrations.push(registration(method, options.rename));
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::std::convert::From<#self_ty>
+            for ::libzrpc::server::service::ZRpcService #where_clause
+        {
+            fn from(state: #self_ty) -> Self {
+                let state = ::std::sync::Arc::new(state);
+                let mut service = ::libzrpc::server::service::ZRpcService::new();
+                #(#registrations)*
+                service
+            }
+        }
+    })
+}
+
+/// Removes the `#[zrpc(...)]` attributes of a method, which the compiler
+/// wouldn't accept.
+fn take_options(method: &mut ImplItemFn) -> syn::Result<Options> {
+    let (attrs, rest) = method
+        .attrs
+        .drain(..)
+        .partition::<Vec<_>, _>(|a| a.path().is_ident("zrpc"));
+    method.attrs = rest;
+
+    let mut options = Options::default();
+
+    for attr in attrs {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                options.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported zrpc attribute, expected `skip` or `rename`"))
+            }
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn registration(method: &ImplItemFn, rename: Option<String>) -> TokenStream2 {
+    let ident = &method.sig.ident;
+    let key = rename.unwrap_or_else(|| ident.to_string());
+
+    let mut inputs = method
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            FnArg::Typed(arg) => Some(&*arg.ty),
+            FnArg::Receiver(_) => None,
+        })
+        .peekable();
+
+    // A leading context parameter receives the call's context.
+    let ctx = match inputs.peek() {
+        Some(Type::Reference(ty)) if is_context(&ty.elem) => {
+            inputs.next();
+            quote!(&__ctx,)
+        }
+        Some(ty) if is_context(ty) => {
+            inputs.next();
+            quote!(__ctx,)
+        }
+        _ => quote!(),
+    };
+
+    let types: Vec<_> = inputs.collect();
+    let args: Vec<_> = (0..types.len())
+        .map(|i| format_ident!("__arg{}", i))
+        .collect();
+    let call = if method.sig.asyncness.is_some() {
+        quote!(state.#ident(#ctx #(#args),*).await)
+    } else {
+        quote!(state.#ident(#ctx #(#args),*))
+    };
+
+    quote! {
+        {
+            let state = state.clone();
+
+            service.add_async_proc(#key, move |__ctx, __params| {
+                let state = state.clone();
+
+                async move {
+                    let (#(#args,)*) = ::libzrpc::types::typed::parse_params::<(#(#types,)*)>(
+                        #key,
+                        &__params,
+                    )?;
+
+                    ::libzrpc::types::typed::IntoProcResult::into_proc_result(#call)
+                }
+            });
+        }
+    }
+}
+
+fn is_context(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "ZRpcContext"),
+        _ => false,
+    }
+}
@@ -0,0 +1,89 @@
+use std::{future::Future, time::Duration};
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    client::{ZRpcClient, addr::ZRpcAddr, builder::ZRpcClientBuilder},
+    error::ZRpcError,
+    types::{dt::ZRpcDt, req::ZRpcHeaders},
+};
+
+/// A `ZRpcClient` for synchronous code, which drives its own current-thread
+/// runtime while a call is made. Background work of the client, such as
+/// heartbeats, only runs during calls.
+///
+/// Must not be used from within an async runtime, as blocking on a call
+/// there panics. Dropping it there is fine.
+pub struct BlockingZRpcClient {
+    client: ZRpcClient,
+    /// Only taken when dropped.
+    runtime: Option<Runtime>,
+}
+
+impl BlockingZRpcClient {
+    pub fn new(addr: impl Into<ZRpcAddr>) -> Result<Self, ZRpcError> {
+        Self::from_builder(ZRpcClient::builder(addr))
+    }
+
+    /// Builds the client configured by `builder`, e.g. with TLS or
+    /// reconnects.
+    pub fn from_builder(builder: ZRpcClientBuilder) -> Result<Self, ZRpcError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(ZRpcError::Io)?;
+        let client = runtime.block_on(builder.build())?;
+
+        Ok(Self {
+            client,
+            runtime: Some(runtime),
+        })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime
+            .as_ref()
+            .expect("the runtime is only taken when dropped")
+            .block_on(future)
+    }
+
+    pub fn call(&self, proc: &str, params: Vec<ZRpcDt>) -> Result<ZRpcDt, ZRpcError> {
+        self.block_on(self.client.call(proc, params))
+    }
+
+    pub fn call_timeout(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+        duration: Duration,
+    ) -> Result<ZRpcDt, ZRpcError> {
+        self.block_on(self.client.call_timeout(proc, params, duration))
+    }
+
+    pub fn call_with_headers(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+        headers: ZRpcHeaders,
+    ) -> Result<(ZRpcDt, ZRpcHeaders), ZRpcError> {
+        self.block_on(self.client.call_with_headers(proc, params, headers))
+    }
+
+    pub fn notify(&self, proc: &str, params: Vec<ZRpcDt>) -> Result<(), ZRpcError> {
+        self.block_on(self.client.notify(proc, params))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+}
+
+impl Drop for BlockingZRpcClient {
+    fn drop(&mut self) {
+        // Dropping a runtime waits for its tasks, which panics when done from
+        // within another runtime.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::error::ZRpcError;
+
+/// The address of a TCP server: an IPv4 or IPv6 address and a port, or a
+/// host name and a port. Host names are resolved whenever the client
+/// connects, and the addresses they resolve to are tried in order until one
+/// accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZRpcAddr {
+    Ip(SocketAddr),
+    Host(String, u16),
+}
+
+impl ZRpcAddr {
+    pub(crate) async fn resolve(&self) -> Result<Vec<SocketAddr>, ZRpcError> {
+        match self {
+            ZRpcAddr::Ip(addr) => Ok(vec![*addr]),
+            ZRpcAddr::Host(host, port) => {
+                let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), *port))
+                    .await
+                    .map_err(ZRpcError::Io)?
+                    .collect();
+
+                if addrs.is_empty() {
+                    return Err(ZRpcError::Io(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("{} has no addresses", host),
+                    )));
+                }
+
+                Ok(addrs)
+            }
+        }
+    }
+}
+
+impl From<SocketAddr> for ZRpcAddr {
+    fn from(addr: SocketAddr) -> Self {
+        ZRpcAddr::Ip(addr)
+    }
+}
+
+impl From<(IpAddr, u16)> for ZRpcAddr {
+    fn from(addr: (IpAddr, u16)) -> Self {
+        ZRpcAddr::Ip(addr.into())
+    }
+}
+
+impl From<(Ipv4Addr, u16)> for ZRpcAddr {
+    fn from(addr: (Ipv4Addr, u16)) -> Self {
+        ZRpcAddr::Ip(addr.into())
+    }
+}
+
+impl From<(Ipv6Addr, u16)> for ZRpcAddr {
+    fn from(addr: (Ipv6Addr, u16)) -> Self {
+        ZRpcAddr::Ip(addr.into())
+    }
+}
+
+/// A host given as an IP address needs no resolving.
+impl From<(&str, u16)> for ZRpcAddr {
+    fn from((host, port): (&str, u16)) -> Self {
+        match host.parse::<IpAddr>() {
+            Ok(ip) => ZRpcAddr::Ip((ip, port).into()),
+            Err(_) => ZRpcAddr::Host(host.to_string(), port),
+        }
+    }
+}
+
+impl From<(String, u16)> for ZRpcAddr {
+    fn from((host, port): (String, u16)) -> Self {
+        (host.as_str(), port).into()
+    }
+}
@@ -0,0 +1,340 @@
+use std::{sync::Arc, time::Duration};
+
+#[cfg(feature = "tls")]
+use std::net::{IpAddr, Ipv4Addr};
+
+#[cfg(feature = "tls")]
+use super::addr::ZRpcAddr;
+
+use tokio::sync::{Mutex, watch};
+
+#[cfg(feature = "tls")]
+use crate::transport::tls::{
+    TlsConnect,
+    rustls::{ClientConfig, pki_types::ServerName},
+};
+use crate::{
+    error::ZRpcError,
+    metrics::{Metrics, Observer},
+    transport::{
+        codec::{Codec, ZRpcCodec},
+        compression::{Compression, ZRpcCompression},
+        tcp::TcpTransport,
+    },
+};
+
+use super::{
+    ConnectionState, Endpoint, Shared, Turns, ZRpcClient,
+    conn::Connection,
+    interceptor::{Interceptor, Interceptors},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReconnectPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) backoff: Duration,
+    pub(crate) max_backoff: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Heartbeat {
+    pub(crate) interval: Duration,
+    pub(crate) timeout: Duration,
+}
+
+/// Settings for the socket of a TCP endpoint, left to the OS unless set.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TcpOptions {
+    pub(crate) nodelay: bool,
+    pub(crate) send_buffer_size: Option<u32>,
+    pub(crate) recv_buffer_size: Option<u32>,
+    pub(crate) connect_timeout: Option<Duration>,
+}
+
+#[derive(Clone)]
+pub struct ZRpcClientBuilder {
+    endpoint: Endpoint,
+    timeout: Duration,
+    auth_key: Option<String>,
+    interceptors: Interceptors,
+    reconnect: bool,
+    policy: ReconnectPolicy,
+    compression: Option<ZRpcCompression>,
+    compression_threshold: usize,
+    max_frame_size: usize,
+    write_queue_limit: usize,
+    max_inflight: Option<usize>,
+    ordered: bool,
+    codec: Arc<dyn Codec>,
+    metrics: Observer,
+    heartbeat: Option<Heartbeat>,
+    tcp: TcpOptions,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<ClientConfig>>,
+    #[cfg(feature = "tls")]
+    tls_server_name: Option<ServerName<'static>>,
+}
+
+impl ZRpcClientBuilder {
+    pub(crate) fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            timeout: Duration::from_secs(30),
+            auth_key: None,
+            interceptors: Interceptors::default(),
+            reconnect: false,
+            policy: ReconnectPolicy {
+                max_retries: 5,
+                backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_secs(5),
+            },
+            compression: None,
+            compression_threshold: Compression::DEFAULT_THRESHOLD,
+            max_frame_size: TcpTransport::DEFAULT_MAX_FRAME_SIZE,
+            write_queue_limit: 1024,
+            max_inflight: None,
+            ordered: false,
+            codec: Arc::new(ZRpcCodec::default()),
+            metrics: Observer::default(),
+            heartbeat: None,
+            tcp: TcpOptions::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            tls_server_name: None,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Attaches `key` to every request, to be checked by the server's
+    /// `AuthMiddleware`.
+    pub fn auth_key(mut self, key: &str) -> Self {
+        self.auth_key = Some(key.to_string());
+        self
+    }
+
+    /// Runs `interceptor` on every call, batch, stream, upload and
+    /// notification made through the client, after the interceptors added
+    /// before it.
+    pub fn interceptor<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Re-dials the server with exponential backoff when the connection
+    /// breaks. Only calls made through `ZRpcClient::call_idempotent` are
+    /// re-sent after a reconnect; plain `call`s report the failure, since the
+    /// server may already have executed them.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.policy.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first re-dial attempt, doubled after every failure
+    /// up to `max_backoff`.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.policy.backoff = backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.policy.max_backoff = max_backoff;
+        self
+    }
+
+    /// Compresses requests of at least `compression_threshold` bytes, if the
+    /// server supports `algorithm`. Off by default.
+    pub fn compression(mut self, algorithm: ZRpcCompression) -> Self {
+        self.compression = Some(algorithm);
+        self
+    }
+
+    /// Defaults to 1 KiB.
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// The largest response frame, in bytes, the client accepts. A larger
+    /// one closes the connection, failing the calls still waiting on it.
+    /// Defaults to 16 MiB.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// How many requests may wait to be written to the connection. Once the
+    /// queue is full, calls, batches, streams and notifications wait for it
+    /// to drain, still bounded by their timeout, so callers producing faster
+    /// than the network can take are slowed down instead of buffering
+    /// without limit. At least 1, defaults to 1024.
+    pub fn write_queue_limit(mut self, limit: usize) -> Self {
+        self.write_queue_limit = limit.max(1);
+        self
+    }
+
+    /// How many calls, batches, streams and uploads may wait for their
+    /// answer on the connection at once. Further ones wait for one of them
+    /// to finish, still bounded by their timeout. Unlimited by default.
+    pub fn max_inflight(mut self, max: usize) -> Self {
+        self.max_inflight = Some(max.max(1));
+        self
+    }
+
+    /// Returns the answers of calls and batches in the order their requests
+    /// were sent, holding an answer back until every call sent before it
+    /// has returned, failed or been dropped. The server still runs the calls
+    /// concurrently. Off by default, as a slow call then delays all later
+    /// ones.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// The codec frames are encoded with, which has to match the server's.
+    /// Defaults to `ZRpcCodec::Bincode`.
+    pub fn codec(mut self, codec: impl Codec + 'static) -> Self {
+        self.codec = Arc::new(codec);
+        self
+    }
+
+    /// Pings the server every `interval`, closing the connection if no pong
+    /// arrives within `timeout`, so a connection silently dropped by the
+    /// network is noticed before the next call. With reconnect mode enabled,
+    /// the server is then re-dialed right away. Off by default.
+    pub fn heartbeat(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.heartbeat = Some(Heartbeat { interval, timeout });
+        self
+    }
+
+    /// Disables Nagle's algorithm, so small requests are sent right away
+    /// instead of being held back to be coalesced. Off by default. This and
+    /// the other socket settings only apply to TCP endpoints.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp.nodelay = nodelay;
+        self
+    }
+
+    /// The size of the socket's send buffer (`SO_SNDBUF`), which the OS may
+    /// round or cap.
+    pub fn send_buffer_size(mut self, size: u32) -> Self {
+        self.tcp.send_buffer_size = Some(size);
+        self
+    }
+
+    /// The size of the socket's receive buffer (`SO_RCVBUF`), which the OS
+    /// may round or cap.
+    pub fn recv_buffer_size(mut self, size: u32) -> Self {
+        self.tcp.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// How long to wait for the TCP connection to be established, on the
+    /// first connect and every re-dial, before failing with
+    /// `ZRpcError::TimeoutError`. Unlimited by default, leaving it to the OS.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.tcp.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Reports the client's connections and calls to `metrics`.
+    pub fn metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Observer::new(metrics);
+        self
+    }
+
+    /// Encrypts the connection with TLS. The server certificate is verified
+    /// against the server's host name or IP address unless `tls_server_name`
+    /// is set. `build` fails with `ZRpcError::Tls` if the host name can't be
+    /// verified against.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: Arc<ClientConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Only applies once `tls` is set, whether before or after.
+    #[cfg(feature = "tls")]
+    pub fn tls_server_name(mut self, server_name: ServerName<'static>) -> Self {
+        self.tls_server_name = Some(server_name);
+        self
+    }
+
+    /// The name the server certificate is verified against, unless one is
+    /// given.
+    #[cfg(feature = "tls")]
+    fn server_name(endpoint: &Endpoint) -> Result<ServerName<'static>, ZRpcError> {
+        match endpoint {
+            Endpoint::Tcp(ZRpcAddr::Ip(addr)) => Ok(ServerName::from(addr.ip())),
+            Endpoint::Tcp(ZRpcAddr::Host(host, _)) => ServerName::try_from(host.clone())
+                .map_err(|e| ZRpcError::Tls(format!("invalid server name {}: {}", host, e))),
+            _ => Ok(ServerName::from(IpAddr::V4(Ipv4Addr::LOCALHOST))),
+        }
+    }
+
+    pub async fn build(self) -> Result<ZRpcClient, ZRpcError> {
+        #[cfg(feature = "tls")]
+        let tls = match self.tls {
+            Some(config) => Some(TlsConnect {
+                connector: config.into(),
+                server_name: match self.tls_server_name {
+                    Some(server_name) => server_name,
+                    None => Self::server_name(&self.endpoint)?,
+                },
+            }),
+            None => None,
+        };
+
+        let mut shared = Shared {
+            endpoint: self.endpoint,
+            conn: Mutex::new(None),
+            auth_key: self.auth_key,
+            interceptors: self.interceptors,
+            reconnect: self.reconnect.then_some(self.policy),
+            compression: self.compression.map(|algorithm| Compression {
+                algorithm,
+                threshold: self.compression_threshold,
+            }),
+            max_frame_size: self.max_frame_size,
+            write_queue_limit: self.write_queue_limit,
+            max_inflight: self.max_inflight,
+            turns: self.ordered.then(Turns::default),
+            codec: self.codec,
+            metrics: self.metrics,
+            state: Arc::new(watch::Sender::new(ConnectionState::Connecting)),
+            tcp: self.tcp,
+            #[cfg(feature = "tls")]
+            tls,
+        };
+
+        let transport = shared.connect().await?;
+        *shared.conn.get_mut() = Some(Arc::new(Connection::new(
+            transport,
+            shared.write_queue_limit,
+            shared.max_inflight,
+            &shared.metrics,
+            shared.state.clone(),
+        )));
+
+        let client = ZRpcClient {
+            shared: Arc::new(shared),
+            timeout: self.timeout,
+            deadline: None,
+        };
+
+        if let Some(heartbeat) = self.heartbeat {
+            tokio::spawn(Shared::heartbeat(Arc::downgrade(&client.shared), heartbeat));
+        }
+
+        Ok(client)
+    }
+}
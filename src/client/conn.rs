@@ -0,0 +1,419 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore, mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+
+use super::ConnectionState;
+use crate::{
+    error::ZRpcError,
+    log,
+    metrics::{ConnectionSpan, Observer},
+    transport::{
+        codec::{self, Codec},
+        tcp::{TcpReader, TcpTransport, TcpWriter},
+    },
+    types::{
+        dt::ZRpcDt,
+        frame::{ZRpcReqFrame, ZRpcResFrame},
+        proc_error::ProcedureError,
+        req::ZRpcHeaders,
+    },
+};
+
+pub(crate) enum Pending {
+    Call(oneshot::Sender<(Result<ZRpcDt, ProcedureError>, ZRpcHeaders)>),
+    Stream(mpsc::UnboundedSender<ZRpcResFrame>),
+    Batch(oneshot::Sender<Vec<Result<ZRpcDt, ProcedureError>>>),
+    Ping(oneshot::Sender<()>),
+    /// Receives the credit of an upload, then its answer.
+    Upload(mpsc::UnboundedSender<ZRpcResFrame>),
+}
+
+/// An encoded frame waiting for the writer. Frames are encoded before they
+/// are queued, so that one that can't be only fails its own call.
+struct Outgoing {
+    bytes: Vec<u8>,
+    /// Told once the frame has been written.
+    flushed: Option<oneshot::Sender<()>>,
+    /// The frame's slot in the write queue, freed once it has been written.
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+struct Inflight {
+    next_id: u32,
+    calls: HashMap<u32, Pending>,
+}
+
+impl Inflight {
+    /// Returns a fresh id for the call. After the counter wraps around, ids
+    /// still in use by long-running calls are skipped.
+    fn insert(&mut self, pending: Pending) -> u32 {
+        loop {
+            let id = self.next_id;
+            self.next_id = id.wrapping_add(1);
+
+            if let Entry::Vacant(entry) = self.calls.entry(id) {
+                entry.insert(pending);
+                return id;
+            }
+        }
+    }
+}
+
+/// `None` once the connection is closed, which fails every pending call by
+/// dropping its sender.
+type Shared = Arc<Mutex<Option<Inflight>>>;
+
+/// What calls on a connection wait for before sending. Closed with the
+/// connection, failing the calls still waiting.
+#[derive(Clone)]
+struct Limits {
+    /// Bounds the requests waiting for the writer.
+    slots: Arc<Semaphore>,
+    /// Bounds the calls waiting for their answer.
+    window: Arc<Semaphore>,
+}
+
+impl Limits {
+    fn close(&self) {
+        self.slots.close();
+        self.window.close();
+    }
+}
+
+pub(crate) type StateSender = Arc<watch::Sender<ConnectionState>>;
+
+/// Marks the connection closed. The state is updated under the same lock, so
+/// that it can't overwrite the state of a connection that replaces this one.
+fn shut(inflight: &Shared, limits: &Limits, state: &StateSender) {
+    limits.close();
+    let mut inflight = inflight.lock().unwrap();
+
+    if inflight.take().is_some() {
+        state.send_replace(ConnectionState::Disconnected);
+    }
+}
+
+/// A connection shared by all calls of a client. Requests are queued to a
+/// writer task, and a reader task routes every response to the call waiting
+/// for its id.
+pub(crate) struct Connection {
+    inflight: Shared,
+    frames: mpsc::UnboundedSender<Outgoing>,
+    codec: Arc<dyn Codec>,
+    limits: Limits,
+    /// Set if the connection was closed over an error worth reporting to
+    /// the calls, rather than just the connection closing.
+    failure: Arc<OnceLock<ZRpcError>>,
+    state: StateSender,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+impl Connection {
+    pub(crate) fn new(
+        transport: TcpTransport,
+        queue_limit: usize,
+        max_inflight: Option<usize>,
+        metrics: &Observer,
+        state: StateSender,
+    ) -> Self {
+        let codec = transport.codec();
+        let (reader, writer) = transport.into_split();
+        let inflight = Arc::new(Mutex::new(Some(Inflight {
+            next_id: 0,
+            calls: HashMap::new(),
+        })));
+        let (frames, queue) = mpsc::unbounded_channel();
+
+        let limits = Limits {
+            slots: Arc::new(Semaphore::new(queue_limit)),
+            window: Arc::new(Semaphore::new(
+                max_inflight.unwrap_or(Semaphore::MAX_PERMITS),
+            )),
+        };
+        let writer = tokio::spawn(Self::write(
+            writer,
+            queue,
+            inflight.clone(),
+            limits.clone(),
+            state.clone(),
+        ));
+        let failure = Arc::new(OnceLock::new());
+        let reader = tokio::spawn(Self::read(
+            reader,
+            inflight.clone(),
+            limits.clone(),
+            failure.clone(),
+            state.clone(),
+            metrics.connection(),
+        ));
+        state.send_replace(ConnectionState::Connected);
+
+        Self {
+            inflight,
+            frames,
+            codec,
+            limits,
+            failure,
+            state,
+            reader,
+            writer,
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inflight.lock().unwrap().is_none()
+    }
+
+    /// Why calls on the connection fail once it is closed.
+    pub(crate) fn error(&self) -> ZRpcError {
+        match self.failure.get() {
+            Some(ZRpcError::FrameTooLarge(max)) => ZRpcError::FrameTooLarge(*max),
+            Some(ZRpcError::CodecMismatch { local, peer }) => ZRpcError::CodecMismatch {
+                local: *local,
+                peer: *peer,
+            },
+            _ => closed(),
+        }
+    }
+
+    /// Fails every pending call and stops using the connection, e.g. when the
+    /// server stopped answering heartbeats.
+    pub(crate) fn close(&self) {
+        shut(&self.inflight, &self.limits, &self.state);
+        self.reader.abort();
+        self.writer.abort();
+    }
+
+    pub(crate) fn register(&self, pending: Pending) -> Result<u32, ZRpcError> {
+        let mut inflight = self.inflight.lock().unwrap();
+        let inflight = inflight.as_mut().ok_or_else(|| self.error())?;
+
+        Ok(inflight.insert(pending))
+    }
+
+    pub(crate) fn unregister(&self, id: u32) {
+        if let Some(inflight) = self.inflight.lock().unwrap().as_mut() {
+            inflight.calls.remove(&id);
+        }
+    }
+
+    /// Waits until fewer calls than the window allows are waiting for their
+    /// answer. The call holds its place until it is dropped.
+    pub(crate) async fn admit(&self) -> Result<OwnedSemaphorePermit, ZRpcError> {
+        self.limits
+            .window
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| self.error())
+    }
+
+    /// Waits for a free slot in the write queue, before a request is built.
+    pub(crate) async fn reserve(&self) -> Result<Slot<'_>, ZRpcError> {
+        let permit = self
+            .limits
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| self.error())?;
+
+        Ok(Slot { conn: self, permit })
+    }
+
+    /// Queues a request, waiting while the write queue is full.
+    pub(crate) async fn send(&self, frame: ZRpcReqFrame) -> Result<(), ZRpcError> {
+        self.reserve().await?.send(frame)
+    }
+
+    /// Like `send`, but waits until the frame has been written.
+    pub(crate) async fn send_flushed(&self, frame: ZRpcReqFrame) -> Result<(), ZRpcError> {
+        let (tx, rx) = oneshot::channel();
+
+        self.reserve().await?.queue(frame, Some(tx))?;
+
+        rx.await.map_err(|_| self.error())
+    }
+
+    /// Queues a frame about calls already sent, such as stream credit, right
+    /// away. These are small, and holding them back behind new requests
+    /// could stall the calls they are about.
+    pub(crate) fn send_control(&self, frame: ZRpcReqFrame) -> Result<(), ZRpcError> {
+        self.frames
+            .send(Outgoing {
+                bytes: codec::encode_frame(&*self.codec, &frame)?,
+                flushed: None,
+                _slot: None,
+            })
+            .map_err(|_| self.error())
+    }
+
+    async fn write(
+        mut writer: TcpWriter,
+        mut queue: mpsc::UnboundedReceiver<Outgoing>,
+        inflight: Shared,
+        limits: Limits,
+        state: StateSender,
+    ) {
+        while let Some(outgoing) = queue.recv().await {
+            if let Err(e) = writer.send(&outgoing.bytes).await {
+                log!("[ZRpcClient] Failed to send frame: {}", e);
+                shut(&inflight, &limits, &state);
+                return;
+            }
+
+            if let Some(flushed) = outgoing.flushed {
+                let _ = flushed.send(());
+            }
+        }
+
+        let _ = writer.shutdown().await;
+    }
+
+    async fn read(
+        mut reader: TcpReader,
+        inflight: Shared,
+        limits: Limits,
+        failure: Arc<OnceLock<ZRpcError>>,
+        state: StateSender,
+        _connection: ConnectionSpan,
+    ) {
+        loop {
+            let frame = match reader.receive_frame::<ZRpcResFrame>().await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    log!("[ZRpcClient] Connection closed: {}", e);
+
+                    if matches!(
+                        e,
+                        ZRpcError::FrameTooLarge(_) | ZRpcError::CodecMismatch { .. }
+                    ) {
+                        let _ = failure.set(e);
+                    }
+                    break;
+                }
+            };
+
+            let mut inflight = inflight.lock().unwrap();
+            let Some(inflight) = inflight.as_mut() else {
+                return;
+            };
+
+            // Answers to calls that timed out or were dropped are discarded.
+            match frame {
+                ZRpcResFrame::Result(id, res, headers) => match inflight.calls.remove(&id) {
+                    Some(Pending::Call(tx)) => {
+                        let _ = tx.send((res, headers));
+                    }
+                    Some(Pending::Upload(tx)) => {
+                        let _ = tx.send(ZRpcResFrame::Result(id, res, headers));
+                    }
+                    _ => {}
+                },
+                ZRpcResFrame::Chunk(id, _) => {
+                    if let Some(Pending::Stream(tx)) = inflight.calls.get(&id) {
+                        let _ = tx.send(frame);
+                    }
+                }
+                ZRpcResFrame::End(id, _) => {
+                    if let Some(Pending::Stream(tx)) = inflight.calls.remove(&id) {
+                        let _ = tx.send(frame);
+                    }
+                }
+                ZRpcResFrame::Batch(id, results) => {
+                    if let Some(Pending::Batch(tx)) = inflight.calls.remove(&id) {
+                        let _ = tx.send(results);
+                    }
+                }
+                ZRpcResFrame::Credit(id, _) => {
+                    if let Some(Pending::Upload(tx)) = inflight.calls.get(&id) {
+                        let _ = tx.send(frame);
+                    }
+                }
+                ZRpcResFrame::Pong(id) => {
+                    if let Some(Pending::Ping(tx)) = inflight.calls.remove(&id) {
+                        let _ = tx.send(());
+                    }
+                }
+            }
+        }
+
+        shut(&inflight, &limits, &state);
+    }
+}
+
+/// A reserved place in the write queue of a connection.
+pub(crate) struct Slot<'a> {
+    conn: &'a Connection,
+    permit: OwnedSemaphorePermit,
+}
+
+impl Slot<'_> {
+    pub(crate) fn send(self, frame: ZRpcReqFrame) -> Result<(), ZRpcError> {
+        self.queue(frame, None)
+    }
+
+    fn queue(
+        self,
+        frame: ZRpcReqFrame,
+        flushed: Option<oneshot::Sender<()>>,
+    ) -> Result<(), ZRpcError> {
+        self.conn
+            .frames
+            .send(Outgoing {
+                bytes: codec::encode_frame(&*self.conn.codec, &frame)?,
+                flushed,
+                _slot: Some(self.permit),
+            })
+            .map_err(|_| self.conn.error())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+pub(crate) fn closed() -> ZRpcError {
+    ZRpcError::Io(std::io::Error::new(
+        std::io::ErrorKind::ConnectionAborted,
+        "connection closed",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ping() -> Pending {
+        Pending::Ping(oneshot::channel().0)
+    }
+
+    #[test]
+    fn ids_wrap_around_and_skip_those_in_use() {
+        let mut inflight = Inflight {
+            next_id: u32::MAX - 1,
+            calls: HashMap::new(),
+        };
+        // Left over from before the counter wrapped.
+        inflight.calls.insert(0, ping());
+        inflight.calls.insert(2, ping());
+
+        assert_eq!(inflight.insert(ping()), u32::MAX - 1);
+        assert_eq!(inflight.insert(ping()), u32::MAX);
+        assert_eq!(inflight.insert(ping()), 1);
+        assert_eq!(inflight.insert(ping()), 3);
+
+        inflight.calls.remove(&0);
+        inflight.next_id = 0;
+        assert_eq!(inflight.insert(ping()), 0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::{
+    error::ZRpcError,
+    types::{dt::ZRpcDt, req::ZRpcReq},
+};
+
+/// The client side counterpart of server middleware, added with
+/// `ZRpcClientBuilder::interceptor`.
+pub trait Interceptor: Send + Sync {
+    /// Runs before the request is sent. Interceptors may rewrite it, e.g. to
+    /// add default headers or check its params. Returning an error fails the
+    /// call with it, without anything being sent.
+    fn before_call(&self, _req: &mut ZRpcReq) -> Result<(), ZRpcError> {
+        Ok(())
+    }
+
+    /// Runs with the result of the call, which may be rewritten before it is
+    /// returned, e.g. to map errors. A notification passes `ZRpcDt::Null`
+    /// once it has been sent. A stream passes its end, `ZRpcDt::Null` or the
+    /// error that ended it, and anything else left here is yielded as its
+    /// last item. Not called for calls dropped before they finish, nor for
+    /// batches failing as a whole.
+    fn after_call(&self, _req: &ZRpcReq, _res: &mut Result<ZRpcDt, ZRpcError>) {}
+}
+
+/// Runs `before_call` hooks in the order interceptors were added, stopping
+/// at the first one that fails the call, then `after_call` hooks in reverse
+/// order for every interceptor whose `before_call` succeeded.
+#[derive(Clone, Default)]
+pub(crate) struct Interceptors {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+}
+
+impl Interceptors {
+    pub(crate) fn push<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.interceptors.push(Arc::new(interceptor));
+    }
+
+    pub(crate) fn enter(&self, req: &mut ZRpcReq) -> (usize, Result<(), ZRpcError>) {
+        for (i, interceptor) in self.interceptors.iter().enumerate() {
+            if let Err(e) = interceptor.before_call(req) {
+                return (i, Err(e));
+            }
+        }
+
+        (self.interceptors.len(), Ok(()))
+    }
+
+    pub(crate) fn leave(&self, entered: usize, req: &ZRpcReq, res: &mut Result<ZRpcDt, ZRpcError>) {
+        for interceptor in self.interceptors[..entered].iter().rev() {
+            interceptor.after_call(req, res);
+        }
+    }
+}
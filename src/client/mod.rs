@@ -0,0 +1,1095 @@
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+
+use futures::{Stream, StreamExt};
+use tokio::{
+    net::{TcpSocket, TcpStream},
+    sync::{Mutex, OwnedSemaphorePermit, mpsc, oneshot, watch},
+    time::timeout,
+};
+
+use crate::{
+    error::ZRpcError,
+    log,
+    metrics::{CallOutcome, CallSpan, Observer},
+    server::ZRpcServerHandle,
+    transport::{
+        AsyncStream,
+        codec::Codec,
+        compression::Compression,
+        handshake::{Hello, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        tcp::TcpTransport,
+    },
+    types::{
+        ctx::ZRpcContext,
+        dt::ZRpcDt,
+        frame::{ZRpcReqFrame, ZRpcResFrame},
+        req::{ZRpcHeaders, ZRpcReq},
+    },
+};
+
+#[cfg(feature = "tls")]
+use crate::transport::tls::{TlsConnect, rustls::ClientConfig};
+
+use addr::ZRpcAddr;
+use builder::{Heartbeat, ReconnectPolicy, TcpOptions, ZRpcClientBuilder};
+use conn::{Connection, Pending, StateSender};
+use interceptor::Interceptors;
+
+pub mod addr;
+pub mod builder;
+mod conn;
+pub mod interceptor;
+pub mod pool;
+
+const STREAM_WINDOW: u32 = 16;
+/// Larger chunks of an upload are split, so that the server's window of
+/// chunks bounds how much of the upload it buffers.
+const UPLOAD_CHUNK: usize = 64 * 1024;
+
+type ResultStream = Pin<Box<dyn Stream<Item = Result<ZRpcDt, ZRpcError>> + Send>>;
+
+/// Calls may be issued concurrently through a shared reference; they are
+/// multiplexed over a single connection and answered in any order. Clones
+/// share that connection, so a client can be handed to many tasks.
+///
+/// Requests are written to the connection in the order they are sent, but
+/// the server runs them concurrently, so one may finish before another
+/// sent earlier. Built with `ordered`, the client returns answers in the
+/// order of the requests regardless.
+#[derive(Clone)]
+pub struct ZRpcClient {
+    shared: Arc<Shared>,
+    timeout: Duration,
+    deadline: Option<Instant>,
+}
+
+/// What the client currently believes about its connection, see
+/// `ZRpcClient::state_changes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The server is being dialed, or re-dialed in reconnect mode.
+    Connecting,
+    Connected,
+    /// The connection was lost or could not be opened. The next call
+    /// re-dials the server.
+    Disconnected,
+}
+
+#[derive(Clone)]
+pub(crate) enum Endpoint {
+    Tcp(ZRpcAddr),
+    Memory(ZRpcServerHandle),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// A stream supplied by the caller, which can only be connected once.
+    Stream(Arc<std::sync::Mutex<Option<Box<dyn AsyncStream>>>>),
+}
+
+struct Shared {
+    endpoint: Endpoint,
+    conn: Mutex<Option<Arc<Connection>>>,
+    auth_key: Option<String>,
+    interceptors: Interceptors,
+    reconnect: Option<ReconnectPolicy>,
+    compression: Option<Compression>,
+    max_frame_size: usize,
+    write_queue_limit: usize,
+    max_inflight: Option<usize>,
+    /// Set in ordered mode.
+    turns: Option<Turns>,
+    codec: Arc<dyn Codec>,
+    metrics: Observer,
+    state: StateSender,
+    tcp: TcpOptions,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnect>,
+}
+
+impl ZRpcClient {
+    /// Connects to `addr`, e.g. `(Ipv4Addr::LOCALHOST, 3000)` or
+    /// `("example.com", 3000)`.
+    pub async fn new(addr: impl Into<ZRpcAddr>) -> Result<Self, ZRpcError> {
+        Self::builder(addr).build().await
+    }
+
+    #[cfg(feature = "tls")]
+    pub async fn new_tls(
+        addr: impl Into<ZRpcAddr>,
+        config: Arc<ClientConfig>,
+    ) -> Result<Self, ZRpcError> {
+        Self::builder(addr).tls(config).build().await
+    }
+
+    pub fn builder(addr: impl Into<ZRpcAddr>) -> ZRpcClientBuilder {
+        ZRpcClientBuilder::new(Endpoint::Tcp(addr.into()))
+    }
+
+    /// Connects to a server in the same process without a socket. TLS
+    /// settings are ignored for such connections.
+    pub async fn new_in_memory(server: &ZRpcServerHandle) -> Result<Self, ZRpcError> {
+        Self::builder_in_memory(server).build().await
+    }
+
+    pub fn builder_in_memory(server: &ZRpcServerHandle) -> ZRpcClientBuilder {
+        ZRpcClientBuilder::new(Endpoint::Memory(server.clone()))
+    }
+
+    /// Connects to a server listening on a Unix domain socket, see
+    /// `ZRpcServer::new_unix`. TLS settings are ignored for such connections.
+    #[cfg(unix)]
+    pub async fn new_unix(path: impl AsRef<Path>) -> Result<Self, ZRpcError> {
+        Self::builder_unix(path).build().await
+    }
+
+    #[cfg(unix)]
+    pub fn builder_unix(path: impl AsRef<Path>) -> ZRpcClientBuilder {
+        ZRpcClientBuilder::new(Endpoint::Unix(path.as_ref().to_path_buf()))
+    }
+
+    /// Talks to a server over an already connected `stream`. Since it can't
+    /// be re-dialed, the client fails once the stream closes, whether
+    /// reconnect mode is enabled or not.
+    pub async fn from_stream<S: AsyncStream + 'static>(stream: S) -> Result<Self, ZRpcError> {
+        Self::builder_from_stream(stream).build().await
+    }
+
+    pub fn builder_from_stream<S: AsyncStream + 'static>(stream: S) -> ZRpcClientBuilder {
+        ZRpcClientBuilder::new(Endpoint::Stream(Arc::new(std::sync::Mutex::new(Some(
+            Box::new(stream),
+        )))))
+    }
+
+    /// Whether the connection is currently open. A server that went away
+    /// silently is only noticed by a failing call or the heartbeat.
+    pub fn is_connected(&self) -> bool {
+        self.connection_state() == ConnectionState::Connected
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.shared.state.borrow()
+    }
+
+    /// Follows the connection state as it changes, e.g. to react to the
+    /// connection dropping.
+    pub fn state_changes(&self) -> watch::Receiver<ConnectionState> {
+        self.shared.state.subscribe()
+    }
+
+    /// Only affects this clone of the client.
+    pub fn set_timeout(&mut self, duration: Duration) {
+        self.timeout = duration;
+    }
+
+    /// A clone of the client whose calls give up at `deadline`, or at its
+    /// own deadline if that comes first. The timeout of each call is clamped
+    /// to the time left, so the server sees the same deadline, and calls
+    /// made once it has passed fail with `ZRpcError::TimeoutError` without
+    /// being sent.
+    pub fn with_deadline(&self, deadline: Instant) -> Self {
+        Self {
+            deadline: Some(self.deadline.map_or(deadline, |own| own.min(deadline))),
+            ..self.clone()
+        }
+    }
+
+    /// A clone of the client bound to the deadline of the call `ctx`
+    /// belongs to, if it has one, so that a procedure calling another server
+    /// doesn't keep it working after its own caller gave up.
+    pub fn with_context(&self, ctx: &ZRpcContext) -> Self {
+        match ctx.deadline() {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self.clone(),
+        }
+    }
+
+    /// `duration` clamped to the time left until the deadline.
+    fn budget(&self, duration: Duration) -> Result<Duration, ZRpcError> {
+        match self.deadline {
+            Some(deadline) => match deadline.saturating_duration_since(Instant::now()) {
+                Duration::ZERO => Err(ZRpcError::TimeoutError),
+                remaining => Ok(duration.min(remaining)),
+            },
+            None => Ok(duration),
+        }
+    }
+
+    pub async fn call(&self, proc: &str, params: Vec<ZRpcDt>) -> Result<ZRpcDt, ZRpcError> {
+        self.execute(self.req(proc, params), self.timeout, false)
+            .await
+            .map(|(dt, _)| dt)
+    }
+
+    pub async fn call_timeout(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+        duration: Duration,
+    ) -> Result<ZRpcDt, ZRpcError> {
+        self.execute(self.req(proc, params), duration, false)
+            .await
+            .map(|(dt, _)| dt)
+    }
+
+    /// Like `call`, but sends `headers` along with the request, and returns
+    /// the headers the server answered with next to the result.
+    pub async fn call_with_headers(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+        headers: ZRpcHeaders,
+    ) -> Result<(ZRpcDt, ZRpcHeaders), ZRpcError> {
+        let req = ZRpcReq {
+            headers,
+            ..self.req(proc, params)
+        };
+
+        self.execute(req, self.timeout, false).await
+    }
+
+    /// Like `call`, but when reconnect mode is enabled and the connection
+    /// breaks mid-call, the request is re-sent once over a fresh connection.
+    /// Only use it for procedures that are safe to execute twice.
+    pub async fn call_idempotent(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+    ) -> Result<ZRpcDt, ZRpcError> {
+        self.execute(self.req(proc, params), self.timeout, true)
+            .await
+            .map(|(dt, _)| dt)
+    }
+
+    /// Calls a procedure without waiting for it to run, returning once the
+    /// request has been written to the connection. The server never answers
+    /// a notification; if the procedure fails, the error is only logged on
+    /// the server when its `log` feature is enabled.
+    pub async fn notify(&self, proc: &str, params: Vec<ZRpcDt>) -> Result<(), ZRpcError> {
+        let mut req = self.req(proc, params);
+        let (entered, res) = self.shared.interceptors.enter(&mut req);
+        let span = self.shared.metrics.call(&req.proc);
+
+        let res = match res.and_then(|_| self.budget(self.timeout)) {
+            Ok(duration) => timeout(duration, async {
+                let conn = self.shared.connection().await?;
+
+                conn.send_flushed(ZRpcReqFrame::Notify(req.clone())).await
+            })
+            .await
+            .map_err(|_| ZRpcError::TimeoutError)
+            .and_then(|res| res),
+            Err(e) => Err(e),
+        };
+
+        let mut res = res.map(|_| ZRpcDt::Null);
+        self.shared.interceptors.leave(entered, &req, &mut res);
+        let res = res.map(|_| ());
+
+        span.end(CallOutcome::of_call(&res));
+        res
+    }
+
+    /// Sends several calls in a single frame, saving a round trip per call.
+    /// The results come back in the order of `calls`, one per call, and a
+    /// failing call only fails its own entry.
+    pub async fn call_batch(
+        &self,
+        calls: Vec<(&str, Vec<ZRpcDt>)>,
+    ) -> Result<Vec<Result<ZRpcDt, ZRpcError>>, ZRpcError> {
+        let budget = self.budget(self.timeout);
+        let mut reqs: Vec<ZRpcReq> = calls
+            .into_iter()
+            .map(|(proc, params)| ZRpcReq {
+                timeout: budget.as_ref().ok().copied(),
+                ..self.req(proc, params)
+            })
+            .collect();
+        // A call that can't be encoded would fail the whole frame, so it is
+        // failed on its own, like one failed by an interceptor.
+        let entered: Vec<_> = reqs
+            .iter_mut()
+            .map(|req| {
+                let (entered, res) = self.shared.interceptors.enter(req);
+                let res = res.and_then(|_| {
+                    req.params
+                        .iter()
+                        .try_for_each(ZRpcDt::check_depth)
+                        .map_err(ZRpcError::Serialization)
+                });
+
+                (entered, res)
+            })
+            .collect();
+        let spans: Vec<CallSpan> = reqs
+            .iter()
+            .map(|req| self.shared.metrics.call(&req.proc))
+            .collect();
+
+        // Calls failed before sending are left out of the frame.
+        let accepted: Vec<ZRpcReq> = reqs
+            .iter()
+            .zip(&entered)
+            .filter(|(_, (_, res))| res.is_ok())
+            .map(|(req, _)| req.clone())
+            .collect();
+
+        let res = match budget {
+            Ok(_) if accepted.is_empty() && !reqs.is_empty() => Ok(Vec::new()),
+            Ok(duration) => self.batch(accepted, duration).await,
+            Err(e) => Err(e),
+        };
+
+        let res = res.map(|results| {
+            let mut results = results.into_iter();
+
+            reqs.iter()
+                .zip(entered)
+                .map(|(req, (entered, accepted))| {
+                    // `batch` checked that every accepted call was answered.
+                    let mut res = match accepted {
+                        Ok(()) => results.next().unwrap(),
+                        Err(e) => Err(e),
+                    };
+                    self.shared.interceptors.leave(entered, req, &mut res);
+                    res
+                })
+                .collect::<Vec<_>>()
+        });
+
+        match &res {
+            Ok(results) => {
+                for (span, res) in spans.into_iter().zip(results) {
+                    span.end(CallOutcome::of_call(res));
+                }
+            }
+            Err(e) => {
+                for span in spans {
+                    span.end(CallOutcome::of_error(e));
+                }
+            }
+        }
+
+        res
+    }
+
+    async fn batch(
+        &self,
+        reqs: Vec<ZRpcReq>,
+        duration: Duration,
+    ) -> Result<Vec<Result<ZRpcDt, ZRpcError>>, ZRpcError> {
+        let len = reqs.len();
+
+        let results = timeout(duration, async {
+            let conn = self.shared.connection().await?;
+            let _permit = conn.admit().await?;
+
+            let (tx, rx) = oneshot::channel();
+            let id = conn.register(Pending::Batch(tx))?;
+            let _guard = CallGuard { conn: &conn, id };
+
+            let slot = conn.reserve().await?;
+            let turn = self.shared.turn();
+            slot.send(ZRpcReqFrame::Batch(id, reqs))?;
+
+            let res = rx.await;
+            turn.wait().await;
+            res.map_err(|_| conn.error())
+        })
+        .await
+        .map_err(|_| ZRpcError::TimeoutError)??;
+
+        if results.len() != len {
+            return Err(ZRpcError::Serialization(format!(
+                "Expected {} batch results, found {}",
+                len,
+                results.len()
+            )));
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|res| res.map_err(ZRpcError::from))
+            .collect())
+    }
+
+    /// Calls a stream procedure and yields its chunks as they arrive. The
+    /// server is never more than a small window of chunks ahead of the
+    /// consumer, and dropping the stream early tells the server to stop
+    /// producing. Each chunk is subject to the client timeout. A deadline
+    /// only bounds opening the stream, though the server is told about it.
+    pub async fn call_stream(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+    ) -> Result<impl Stream<Item = Result<ZRpcDt, ZRpcError>> + Unpin + Send + 'static, ZRpcError>
+    {
+        let mut req = self.req(proc, params);
+        let (entered, res) = self.shared.interceptors.enter(&mut req);
+        let span = self.shared.metrics.call(&req.proc);
+
+        let state = async {
+            res?;
+            let duration = self.budget(self.timeout)?;
+            if let Some(deadline) = self.deadline {
+                req.timeout = Some(deadline.saturating_duration_since(Instant::now()));
+            }
+
+            let (conn, permit) = timeout(duration, async {
+                let conn = self.shared.connection().await?;
+                let permit = conn.admit().await?;
+                Ok::<_, ZRpcError>((conn, permit))
+            })
+            .await
+            .map_err(|_| ZRpcError::TimeoutError)??;
+
+            let (tx, chunks) = mpsc::unbounded_channel();
+            let id = conn.register(Pending::Stream(tx))?;
+
+            let state = ClientStream {
+                conn,
+                permit: Some(permit),
+                id,
+                chunks,
+                timeout: self.timeout,
+                received: 0,
+                done: false,
+                ended: false,
+                span: None,
+                interceptors: self.shared.interceptors.clone(),
+                entered,
+                req: req.clone(),
+            };
+
+            state
+                .conn
+                .send(ZRpcReqFrame::Stream(id, req.clone(), STREAM_WINDOW))
+                .await?;
+
+            Ok(state)
+        }
+        .await;
+
+        let stream: ResultStream = match state {
+            Ok(mut state) => {
+                state.span = Some(span);
+
+                Box::pin(futures::stream::unfold(state, |mut state| async move {
+                    state.next().await.map(|item| (item, state))
+                }))
+            }
+            Err(e) => {
+                let mut res = Err(e);
+                self.shared.interceptors.leave(entered, &req, &mut res);
+                span.end(CallOutcome::of_call(&res));
+
+                // An interceptor may have turned the error into the only
+                // item of the stream.
+                Box::pin(futures::stream::iter(
+                    Some(res?).filter(|dt| *dt != ZRpcDt::Null).map(Ok),
+                ))
+            }
+        };
+
+        Ok(stream)
+    }
+
+    /// Calls an upload procedure, sending the chunks of `data` after the
+    /// request as the server reads them, so that a large upload is never
+    /// held in memory all at once. Returns the procedure's answer once it
+    /// has finished. Waiting for the server to take more data, and for the
+    /// answer after the last chunk, are each subject to the client timeout.
+    /// Dropping the future cancels the upload.
+    pub async fn call_upload<S>(
+        &self,
+        proc: &str,
+        params: Vec<ZRpcDt>,
+        data: S,
+    ) -> Result<ZRpcDt, ZRpcError>
+    where
+        S: Stream,
+        S::Item: Into<Vec<u8>>,
+    {
+        let mut req = self.req(proc, params);
+        let (entered, res) = self.shared.interceptors.enter(&mut req);
+        let span = self.shared.metrics.call(&req.proc);
+
+        let mut res = match res {
+            Ok(()) => self.upload(req.clone(), data).await,
+            Err(e) => Err(e),
+        };
+
+        self.shared.interceptors.leave(entered, &req, &mut res);
+        span.end(CallOutcome::of_call(&res));
+        res
+    }
+
+    async fn upload<S>(&self, mut req: ZRpcReq, data: S) -> Result<ZRpcDt, ZRpcError>
+    where
+        S: Stream,
+        S::Item: Into<Vec<u8>>,
+    {
+        let duration = self.budget(self.timeout)?;
+        if let Some(deadline) = self.deadline {
+            req.timeout = Some(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        let (conn, _permit) = timeout(duration, async {
+            let conn = self.shared.connection().await?;
+            let permit = conn.admit().await?;
+            Ok::<_, ZRpcError>((conn, permit))
+        })
+        .await
+        .map_err(|_| ZRpcError::TimeoutError)??;
+
+        let (tx, mut frames) = mpsc::unbounded_channel();
+        let id = conn.register(Pending::Upload(tx))?;
+        let mut guard = UploadGuard {
+            conn: &conn,
+            id,
+            answered: false,
+        };
+
+        conn.send(ZRpcReqFrame::Upload(id, req)).await?;
+
+        let mut data = std::pin::pin!(data.fuse());
+        let mut credit = 0u32;
+        let mut chunk = Vec::new();
+        let mut sent = 0;
+        let mut finished = false;
+
+        loop {
+            let frame = if finished || credit == 0 {
+                timeout(self.timeout, frames.recv())
+                    .await
+                    .map_err(|_| ZRpcError::TimeoutError)?
+            } else if sent < chunk.len() {
+                let piece = if sent == 0 && chunk.len() <= UPLOAD_CHUNK {
+                    std::mem::take(&mut chunk)
+                } else {
+                    let end = chunk.len().min(sent + UPLOAD_CHUNK);
+                    let piece = chunk[sent..end].to_vec();
+                    sent = end;
+                    piece
+                };
+
+                conn.send(ZRpcReqFrame::Data(id, piece)).await?;
+                credit -= 1;
+                continue;
+            } else {
+                // The server may answer before all of the data has been sent.
+                tokio::select! {
+                    frame = frames.recv() => frame,
+                    item = data.next() => {
+                        match item {
+                            Some(item) => {
+                                chunk = item.into();
+                                sent = 0;
+                            }
+                            None => {
+                                conn.send(ZRpcReqFrame::UploadEnd(id)).await?;
+                                finished = true;
+                            }
+                        }
+                        continue;
+                    }
+                }
+            };
+
+            match frame {
+                Some(ZRpcResFrame::Credit(_, n)) => credit = credit.saturating_add(n),
+                Some(ZRpcResFrame::Result(_, res, _)) => {
+                    guard.answered = true;
+                    return res.map_err(ZRpcError::from);
+                }
+                Some(frame) => {
+                    return Err(ZRpcError::Serialization(format!(
+                        "Unexpected frame during upload: {:?}",
+                        frame
+                    )));
+                }
+                None => return Err(conn.error()),
+            }
+        }
+    }
+
+    fn req(&self, proc: &str, params: Vec<ZRpcDt>) -> ZRpcReq {
+        ZRpcReq {
+            auth_key: self.shared.auth_key.clone(),
+            ..ZRpcReq::new(proc, params)
+        }
+    }
+
+    async fn execute(
+        &self,
+        mut req: ZRpcReq,
+        duration: Duration,
+        idempotent: bool,
+    ) -> Result<(ZRpcDt, ZRpcHeaders), ZRpcError> {
+        let (entered, res) = self.shared.interceptors.enter(&mut req);
+        let span = self.shared.metrics.call(&req.proc);
+
+        let res = match res.and_then(|_| self.budget(duration)) {
+            Ok(duration) => {
+                req.timeout = Some(duration);
+
+                timeout(duration, async {
+                    match self.round_trip(&req).await {
+                        Err(ZRpcError::Io(_)) if idempotent && self.shared.reconnect.is_some() => {
+                            self.round_trip(&req).await
+                        }
+                        res => res,
+                    }
+                })
+                .await
+                .map_err(|_| ZRpcError::TimeoutError)
+                .and_then(|res| res)
+            }
+            Err(e) => Err(e),
+        };
+
+        let (mut res, headers) = match res {
+            Ok((dt, headers)) => (Ok(dt), headers),
+            Err(e) => (Err(e), ZRpcHeaders::new()),
+        };
+        self.shared.interceptors.leave(entered, &req, &mut res);
+        let res = res.map(|dt| (dt, headers));
+
+        span.end(CallOutcome::of_call(&res));
+        res
+    }
+
+    async fn round_trip(&self, req: &ZRpcReq) -> Result<(ZRpcDt, ZRpcHeaders), ZRpcError> {
+        let conn = self.shared.connection().await?;
+        let _permit = conn.admit().await?;
+
+        let (tx, rx) = oneshot::channel();
+        let id = conn.register(Pending::Call(tx))?;
+
+        // Dropped on timeout or cancellation, so a late answer is discarded.
+        let _guard = CallGuard { conn: &conn, id };
+
+        // A call waiting for room in the write queue doesn't hold a copy of
+        // its request yet.
+        let slot = conn.reserve().await?;
+        let turn = self.shared.turn();
+        slot.send(ZRpcReqFrame::Call(id, req.clone()))?;
+
+        let res = rx.await;
+        turn.wait().await;
+        let (res, headers) = res.map_err(|_| conn.error())?;
+
+        res.map(|dt| (dt, headers)).map_err(ZRpcError::from)
+    }
+}
+
+impl Shared {
+    /// The place in line of a call about to be sent, which only matters in
+    /// ordered mode.
+    fn turn(&self) -> Turn {
+        match &self.turns {
+            Some(turns) => turns.take(),
+            None => Turn::default(),
+        }
+    }
+
+    async fn connect(&self) -> Result<TcpTransport, ZRpcError> {
+        let mut transport = self.open().await?;
+        transport.set_max_frame_size(self.max_frame_size);
+        transport.set_codec(self.codec.clone());
+
+        transport
+            .send_hello(&Hello::new(PROTOCOL_VERSION, &*self.codec))
+            .await?;
+
+        let hello = match transport.receive_hello().await {
+            Ok(hello) => hello,
+            // Servers that predate the handshake close the connection.
+            Err(ZRpcError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(ZRpcError::IncompatibleProtocol(
+                    "the server closed the connection during the handshake".to_string(),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+
+        Self::check_hello(&hello, &*self.codec)?;
+        transport.set_compression(
+            self.compression
+                .and_then(|c| c.negotiate(&hello.compression())),
+        );
+
+        Ok(transport)
+    }
+
+    fn check_hello(hello: &Hello, codec: &dyn Codec) -> Result<(), ZRpcError> {
+        match hello.codecs.first() {
+            Some(&peer) if peer != codec.tag() => {
+                return Err(ZRpcError::CodecMismatch {
+                    local: codec.tag(),
+                    peer,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some(e) = &hello.error {
+            return Err(ZRpcError::IncompatibleProtocol(e.clone()));
+        }
+
+        if !Hello::is_supported(hello.protocol) {
+            return Err(ZRpcError::IncompatibleProtocol(format!(
+                "the server ({}) speaks protocol {}, this client ({}) speaks {} to {}",
+                hello.version,
+                hello.protocol,
+                env!("CARGO_PKG_VERSION"),
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn open(&self) -> Result<TcpTransport, ZRpcError> {
+        let addr = match &self.endpoint {
+            Endpoint::Tcp(addr) => addr,
+            Endpoint::Memory(server) => return Ok(TcpTransport::new(server.connect()?)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(ZRpcError::Io)?;
+
+                return Ok(TcpTransport::new(stream));
+            }
+            Endpoint::Stream(stream) => {
+                return stream
+                    .lock()
+                    .unwrap()
+                    .take()
+                    .map(TcpTransport::new)
+                    .ok_or_else(conn::closed);
+            }
+        };
+
+        let stream = self.dial(addr).await?;
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let stream = tls
+                .connector
+                .connect(tls.server_name.clone(), stream)
+                .await
+                .map_err(ZRpcError::Io)?;
+
+            return Ok(TcpTransport::new(stream));
+        }
+
+        Ok(TcpTransport::new(stream))
+    }
+
+    /// Connects to the first of the addresses `addr` resolves to that
+    /// accepts. The connect timeout applies to each of them.
+    async fn dial(&self, addr: &ZRpcAddr) -> Result<TcpStream, ZRpcError> {
+        let mut last = None;
+
+        for addr in addr.resolve().await? {
+            match self.dial_one(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    log!("[ZRpcClient] Failed to connect to {}: {}", addr, e);
+                    last = Some(e);
+                }
+            }
+        }
+
+        Err(last.unwrap_or_else(conn::closed))
+    }
+
+    async fn dial_one(&self, addr: SocketAddr) -> Result<TcpStream, ZRpcError> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4(),
+            SocketAddr::V6(_) => TcpSocket::new_v6(),
+        }
+        .map_err(ZRpcError::Io)?;
+        socket
+            .set_nodelay(self.tcp.nodelay)
+            .map_err(ZRpcError::Io)?;
+
+        if let Some(size) = self.tcp.send_buffer_size {
+            socket.set_send_buffer_size(size).map_err(ZRpcError::Io)?;
+        }
+
+        if let Some(size) = self.tcp.recv_buffer_size {
+            socket.set_recv_buffer_size(size).map_err(ZRpcError::Io)?;
+        }
+
+        let connect = socket.connect(addr);
+
+        match self.tcp.connect_timeout {
+            Some(duration) => timeout(duration, connect)
+                .await
+                .map_err(|_| ZRpcError::TimeoutError)?,
+            None => connect.await,
+        }
+        .map_err(ZRpcError::Io)
+    }
+
+    async fn redial(&self) -> Result<TcpTransport, ZRpcError> {
+        let Some(policy) = self.reconnect else {
+            return self.connect().await;
+        };
+
+        let mut backoff = policy.backoff;
+        let mut attempt = 0;
+
+        loop {
+            match self.connect().await {
+                Ok(transport) => return Ok(transport),
+                Err(e) if attempt >= policy.max_retries => return Err(e),
+                Err(e) => {
+                    attempt += 1;
+
+                    log!(
+                        "[ZRpcClient] Reconnect attempt {} failed: {}, retrying in {:?}",
+                        attempt,
+                        e,
+                        backoff
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(policy.max_backoff);
+                }
+            }
+        }
+    }
+
+    /// Runs until every clone of the client is dropped.
+    async fn heartbeat(shared: Weak<Shared>, heartbeat: Heartbeat) {
+        let mut ticks = tokio::time::interval(heartbeat.interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks.tick().await;
+
+        loop {
+            ticks.tick().await;
+
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+
+            let conn = shared.conn.lock().await.clone();
+
+            if let Some(conn) = conn.filter(|conn| !conn.is_closed()) {
+                if shared.ping(&conn, heartbeat.timeout).await {
+                    continue;
+                }
+
+                log!(
+                    "[ZRpcClient] No pong within {:?}, closing connection",
+                    heartbeat.timeout
+                );
+                conn.close();
+            }
+
+            if shared.reconnect.is_some() {
+                let _ = shared.connection().await;
+            }
+        }
+    }
+
+    async fn ping(&self, conn: &Connection, duration: Duration) -> bool {
+        let (tx, rx) = oneshot::channel();
+        let Ok(id) = conn.register(Pending::Ping(tx)) else {
+            return false;
+        };
+        let _guard = CallGuard { conn, id };
+
+        conn.send_control(ZRpcReqFrame::Ping(id)).is_ok()
+            && matches!(timeout(duration, rx).await, Ok(Ok(())))
+    }
+
+    /// Returns the open connection, re-dialing if it has been closed. Calls
+    /// that race here share a single re-dial.
+    async fn connection(&self) -> Result<Arc<Connection>, ZRpcError> {
+        let mut conn = self.conn.lock().await;
+
+        if let Some(conn) = conn.as_ref()
+            && !conn.is_closed()
+        {
+            return Ok(conn.clone());
+        }
+
+        self.state.send_replace(ConnectionState::Connecting);
+
+        let transport = self.redial().await.inspect_err(|_| {
+            self.state.send_replace(ConnectionState::Disconnected);
+        })?;
+
+        Ok(conn
+            .insert(Arc::new(Connection::new(
+                transport,
+                self.write_queue_limit,
+                self.max_inflight,
+                &self.metrics,
+                self.state.clone(),
+            )))
+            .clone())
+    }
+}
+
+/// Hands out turns to calls in the order they are sent, in ordered mode.
+#[derive(Default)]
+struct Turns {
+    last: std::sync::Mutex<Option<oneshot::Receiver<()>>>,
+}
+
+impl Turns {
+    fn take(&self) -> Turn {
+        let (done, next) = oneshot::channel();
+        let prev = self.last.lock().unwrap().replace(next);
+
+        Turn {
+            prev,
+            _done: Some(done),
+        }
+    }
+}
+
+/// A call's place in line. Dropping it lets the next call return.
+#[derive(Default)]
+struct Turn {
+    prev: Option<oneshot::Receiver<()>>,
+    _done: Option<oneshot::Sender<()>>,
+}
+
+impl Turn {
+    /// Waits until the call sent before this one has returned.
+    async fn wait(self) {
+        if let Some(prev) = self.prev {
+            let _ = prev.await;
+        }
+    }
+}
+
+struct CallGuard<'a> {
+    conn: &'a Connection,
+    id: u32,
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.conn.unregister(self.id);
+    }
+}
+
+/// Cancels an upload that is dropped before the server answered it.
+struct UploadGuard<'a> {
+    conn: &'a Connection,
+    id: u32,
+    answered: bool,
+}
+
+impl Drop for UploadGuard<'_> {
+    fn drop(&mut self) {
+        if !self.answered {
+            let _ = self.conn.send_control(ZRpcReqFrame::Cancel(self.id));
+        }
+        self.conn.unregister(self.id);
+    }
+}
+
+struct ClientStream {
+    conn: Arc<Connection>,
+    /// The stream's place in the window of the connection, given up once
+    /// it finishes.
+    permit: Option<OwnedSemaphorePermit>,
+    id: u32,
+    chunks: mpsc::UnboundedReceiver<ZRpcResFrame>,
+    timeout: Duration,
+    received: u32,
+    /// No more items are yielded.
+    done: bool,
+    /// The server has finished the stream, so it needs no cancelling.
+    ended: bool,
+    /// Ends as cancelled if the stream is dropped before it finishes.
+    span: Option<CallSpan>,
+    interceptors: Interceptors,
+    /// How many interceptors the stream passed, see `Interceptors::enter`.
+    entered: usize,
+    req: ZRpcReq,
+}
+
+impl ClientStream {
+    async fn next(&mut self) -> Option<Result<ZRpcDt, ZRpcError>> {
+        if self.done {
+            return None;
+        }
+
+        let res = match timeout(self.timeout, self.chunks.recv()).await {
+            Ok(Some(ZRpcResFrame::Chunk(_, dt))) => {
+                self.received += 1;
+
+                if self.received < STREAM_WINDOW / 2 {
+                    return Some(Ok(dt));
+                }
+
+                self.received = 0;
+                self.conn
+                    .send_control(ZRpcReqFrame::Credit(self.id, STREAM_WINDOW / 2))
+                    .map(|_| dt)
+            }
+            Ok(Some(ZRpcResFrame::End(_, res))) => {
+                self.ended = true;
+                return self.finish(res.map(|_| ZRpcDt::Null).map_err(ZRpcError::from));
+            }
+            Ok(Some(frame)) => Err(ZRpcError::Serialization(format!(
+                "Unexpected frame during stream: {:?}",
+                frame
+            ))),
+            Ok(None) => {
+                self.ended = true;
+                Err(self.conn.error())
+            }
+            Err(_) => Err(ZRpcError::TimeoutError),
+        };
+
+        if res.is_err() {
+            return self.finish(res);
+        }
+
+        Some(res)
+    }
+
+    /// Ends the stream with `res`, passed through the interceptors, which is
+    /// yielded as the last item unless it is left `Ok(ZRpcDt::Null)`.
+    fn finish(&mut self, mut res: Result<ZRpcDt, ZRpcError>) -> Option<Result<ZRpcDt, ZRpcError>> {
+        self.done = true;
+        self.interceptors.leave(self.entered, &self.req, &mut res);
+        self.permit = None;
+
+        if let Some(span) = self.span.take() {
+            span.end(CallOutcome::of_call(&res));
+        }
+
+        match res {
+            Ok(ZRpcDt::Null) => None,
+            res => Some(res),
+        }
+    }
+}
+
+impl Drop for ClientStream {
+    fn drop(&mut self) {
+        if !self.ended {
+            let _ = self.conn.send_control(ZRpcReqFrame::Cancel(self.id));
+            self.conn.unregister(self.id);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Mutex;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::{error::ZRpcError, types::dt::ZRpcDt};
+
+use super::{ZRpcClient, addr::ZRpcAddr, builder::ZRpcClientBuilder};
+
+/// Which connection a call is sent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZRpcPoolStrategy {
+    /// Takes the connections in turn, skipping those that carry as many
+    /// calls as they may.
+    RoundRobin,
+    /// Picks the connection with the fewest calls in flight, so that calls
+    /// avoid connections held up by slow ones.
+    LeastBusy,
+}
+
+pub struct ZRpcClientPoolBuilder {
+    client: ZRpcClientBuilder,
+    max_size: usize,
+    max_calls_per_connection: usize,
+    strategy: ZRpcPoolStrategy,
+    queue: bool,
+}
+
+impl ZRpcClientPoolBuilder {
+    /// Settings used for every connection of the pool.
+    pub fn client(mut self, client: ZRpcClientBuilder) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size.max(1);
+        self
+    }
+
+    /// How many calls may share a connection at once. At least 1, which is
+    /// the default.
+    pub fn max_calls_per_connection(mut self, max: usize) -> Self {
+        self.max_calls_per_connection = max.max(1);
+        self
+    }
+
+    pub fn strategy(mut self, strategy: ZRpcPoolStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// When `true` (the default) a call waits for a connection to become
+    /// available, otherwise it fails with `ZRpcError::PoolExhausted`.
+    pub fn queue(mut self, queue: bool) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    pub async fn build(self) -> Result<ZRpcClientPool, ZRpcError> {
+        let mut clients = Vec::with_capacity(self.max_size);
+
+        for _ in 0..self.max_size {
+            clients.push(self.client.clone().build().await?);
+        }
+
+        Ok(ZRpcClientPool {
+            load: Mutex::new(Load {
+                calls: vec![0; clients.len()],
+                next: 0,
+            }),
+            permits: Semaphore::new(clients.len() * self.max_calls_per_connection),
+            clients,
+            max_calls_per_connection: self.max_calls_per_connection,
+            strategy: self.strategy,
+            queue: self.queue,
+        })
+    }
+}
+
+/// Several connections to the same server, over which calls are spread.
+/// Connections re-dial on their own once they break, and a call that is
+/// dropped midway leaves its connection to the others.
+pub struct ZRpcClientPool {
+    clients: Vec<ZRpcClient>,
+    load: Mutex<Load>,
+    /// One per call the connections can take together, so a call holding a
+    /// permit always finds a connection with room.
+    permits: Semaphore,
+    max_calls_per_connection: usize,
+    strategy: ZRpcPoolStrategy,
+    queue: bool,
+}
+
+struct Load {
+    /// The calls in flight on each connection.
+    calls: Vec<usize>,
+    /// Where the search for a connection starts.
+    next: usize,
+}
+
+impl ZRpcClientPool {
+    pub fn builder(addr: impl Into<ZRpcAddr>) -> ZRpcClientPoolBuilder {
+        ZRpcClientPoolBuilder {
+            client: ZRpcClient::builder(addr),
+            max_size: 4,
+            max_calls_per_connection: 1,
+            strategy: ZRpcPoolStrategy::RoundRobin,
+            queue: true,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub async fn call(&self, proc: &str, params: Vec<ZRpcDt>) -> Result<ZRpcDt, ZRpcError> {
+        let permit = if self.queue {
+            self.permits.acquire().await.ok()
+        } else {
+            self.permits.try_acquire().ok()
+        }
+        .ok_or(ZRpcError::PoolExhausted)?;
+
+        let lease = Lease {
+            pool: self,
+            index: self.take(),
+            _permit: permit,
+        };
+
+        self.clients[lease.index].call(proc, params).await
+    }
+
+    /// Counts a call on the connection the strategy picks.
+    fn take(&self) -> usize {
+        let mut load = self.load.lock().unwrap();
+        let size = load.calls.len();
+        let mut order = (0..size).map(|i| (load.next + i) % size);
+
+        let index = match self.strategy {
+            ZRpcPoolStrategy::RoundRobin => {
+                order.find(|&i| load.calls[i] < self.max_calls_per_connection)
+            }
+            ZRpcPoolStrategy::LeastBusy => order.min_by_key(|&i| load.calls[i]),
+        }
+        .expect("a permit leaves room on some connection");
+
+        load.calls[index] += 1;
+        load.next = (index + 1) % size;
+        index
+    }
+}
+
+/// A call counted on a connection, until it ends or is dropped.
+struct Lease<'a> {
+    pool: &'a ZRpcClientPool,
+    index: usize,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        // The permit is only released after the call is no longer counted,
+        // so a call holding a permit always finds room.
+        self.pool.load.lock().unwrap().calls[self.index] -= 1;
+    }
+}
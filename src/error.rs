@@ -1,9 +1,13 @@
 use std::{
     error::Error,
     fmt::{Display, Formatter},
+    time::Duration,
 };
 
-use crate::types::proc_error::ProcedureError;
+use crate::{
+    transport::codec,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
 
 #[derive(Debug)]
 pub enum ZRpcError {
@@ -11,6 +15,79 @@ pub enum ZRpcError {
     Serialization(String),
     TimeoutError,
     Procedure(ProcedureError),
+    PoolExhausted,
+    /// A received frame was larger than the limit, in bytes, of the receiving
+    /// side. The connection is closed, since the rest of the frame is unread.
+    FrameTooLarge(usize),
+    /// The peer encodes frames with another codec than this side, either
+    /// given by its tag.
+    CodecMismatch {
+        local: u8,
+        peer: u8,
+    },
+    #[cfg(feature = "tls")]
+    Tls(String),
+    /// The handshake found that the peers can't talk to each other, e.g.
+    /// because they speak different protocol versions.
+    IncompatibleProtocol(String),
+    /// The server turned the call away because this client made too many,
+    /// suggesting how long to wait before trying again.
+    RateLimited(Duration),
+    /// The procedure is running at its concurrency limit, and too many calls
+    /// are already waiting for it.
+    Overloaded,
+    /// The procedure ran and failed with an `Application` error, with the
+    /// details it attached, if any.
+    Application {
+        code: u32,
+        message: String,
+        data: Option<ZRpcDt>,
+    },
+    /// A client interceptor turned the call down before it was sent.
+    Rejected(String),
+}
+
+impl ZRpcError {
+    /// Whether the same call may succeed if issued again. Errors returned by
+    /// the procedure itself are considered permanent.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ZRpcError::Io(_)
+            | ZRpcError::TimeoutError
+            | ZRpcError::PoolExhausted
+            | ZRpcError::RateLimited(_)
+            | ZRpcError::Overloaded => true,
+            ZRpcError::Procedure(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// How long the server asked to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ZRpcError::RateLimited(after) => Some(*after),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProcedureError> for ZRpcError {
+    fn from(err: ProcedureError) -> Self {
+        match err {
+            ProcedureError::RateLimited(after) => ZRpcError::RateLimited(after),
+            ProcedureError::Overloaded => ZRpcError::Overloaded,
+            ProcedureError::Application {
+                code,
+                message,
+                data,
+            } => ZRpcError::Application {
+                code,
+                message,
+                data,
+            },
+            e => ZRpcError::Procedure(e),
+        }
+    }
 }
 
 impl From<std::io::Error> for ZRpcError {
@@ -26,6 +103,32 @@ impl Display for ZRpcError {
             ZRpcError::Serialization(e) => write!(f, "SerializationError: {}", e),
             ZRpcError::TimeoutError => write!(f, "Timeout"),
             ZRpcError::Procedure(e) => write!(f, "ProcedureError: {}", e),
+            ZRpcError::PoolExhausted => write!(f, "PoolExhausted"),
+            ZRpcError::FrameTooLarge(max) => {
+                write!(f, "FrameTooLarge: frame exceeds the limit of {} bytes", max)
+            }
+            ZRpcError::CodecMismatch { local, peer } => write!(
+                f,
+                "CodecMismatch: the peer encodes frames with {}, this side with {}",
+                codec::describe(*peer),
+                codec::describe(*local)
+            ),
+            #[cfg(feature = "tls")]
+            ZRpcError::Tls(e) => write!(f, "TlsError: {}", e),
+            ZRpcError::IncompatibleProtocol(e) => write!(f, "IncompatibleProtocol: {}", e),
+            ZRpcError::RateLimited(after) => write!(f, "RateLimited: retry after {:?}", after),
+            ZRpcError::Overloaded => write!(f, "Overloaded"),
+            ZRpcError::Application {
+                code,
+                message,
+                data: None,
+            } => write!(f, "ApplicationError({}): {}", code, message),
+            ZRpcError::Application {
+                code,
+                message,
+                data: Some(data),
+            } => write!(f, "ApplicationError({}): {} {:?}", code, message, data),
+            ZRpcError::Rejected(e) => write!(f, "Rejected: {}", e),
         }
     }
 }
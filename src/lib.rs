@@ -1,8 +1,11 @@
 #![allow(incomplete_features)]
 #![feature(specialization)]
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
 pub mod error;
+pub mod metrics;
 pub mod middleware;
 pub mod server;
 pub mod transport;
@@ -0,0 +1,128 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{error::ZRpcError, types::proc_error::ProcedureError};
+
+/// Observes connections and calls, on the server or on a client. Every hook
+/// defaults to doing nothing, and nothing is measured unless an observer is
+/// registered. Hooks run inline with the calls, so they should be cheap.
+pub trait Metrics: Send + Sync {
+    fn on_connection_open(&self) {}
+
+    fn on_connection_close(&self) {}
+
+    fn on_call_start(&self, _proc: &str) {}
+
+    /// Runs once for every `on_call_start`, with the time since then.
+    fn on_call_end(&self, _proc: &str, _duration: Duration, _outcome: CallOutcome) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallOutcome {
+    Success,
+    /// The procedure returned an error, or the call was rejected before it
+    /// ran.
+    Error,
+    /// No answer arrived in time. Only seen by clients.
+    Timeout,
+    /// The connection failed before the call was answered.
+    Disconnected,
+    /// The call was dropped before it finished, by the caller or by the
+    /// server shutting down.
+    Cancelled,
+}
+
+impl CallOutcome {
+    /// A lowercase name, e.g. for metric labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallOutcome::Success => "success",
+            CallOutcome::Error => "error",
+            CallOutcome::Timeout => "timeout",
+            CallOutcome::Disconnected => "disconnected",
+            CallOutcome::Cancelled => "cancelled",
+        }
+    }
+
+    pub(crate) fn of<T>(res: &Result<T, ProcedureError>) -> Self {
+        match res {
+            Ok(_) => CallOutcome::Success,
+            Err(_) => CallOutcome::Error,
+        }
+    }
+
+    pub(crate) fn of_call<T>(res: &Result<T, ZRpcError>) -> Self {
+        match res {
+            Ok(_) => CallOutcome::Success,
+            Err(e) => Self::of_error(e),
+        }
+    }
+
+    pub(crate) fn of_error(err: &ZRpcError) -> Self {
+        match err {
+            ZRpcError::TimeoutError => CallOutcome::Timeout,
+            ZRpcError::Io(_) => CallOutcome::Disconnected,
+            _ => CallOutcome::Error,
+        }
+    }
+}
+
+/// The registered observer, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Observer(Option<Arc<dyn Metrics>>);
+
+impl Observer {
+    pub(crate) fn new<M: Metrics + 'static>(metrics: M) -> Self {
+        Self(Some(Arc::new(metrics)))
+    }
+
+    /// Reports the connection as open until the returned guard is dropped.
+    pub(crate) fn connection(&self) -> ConnectionSpan {
+        if let Some(metrics) = &self.0 {
+            metrics.on_connection_open();
+        }
+
+        ConnectionSpan(self.0.clone())
+    }
+
+    /// Reports the call as started. It ends with `CallSpan::end`, or as
+    /// cancelled if the span is dropped first.
+    pub(crate) fn call(&self, proc: &str) -> CallSpan {
+        CallSpan(self.0.as_ref().map(|metrics| {
+            metrics.on_call_start(proc);
+            (metrics.clone(), proc.to_string(), Instant::now())
+        }))
+    }
+}
+
+pub(crate) struct ConnectionSpan(Option<Arc<dyn Metrics>>);
+
+impl Drop for ConnectionSpan {
+    fn drop(&mut self) {
+        if let Some(metrics) = &self.0 {
+            metrics.on_connection_close();
+        }
+    }
+}
+
+pub(crate) struct CallSpan(Option<(Arc<dyn Metrics>, String, Instant)>);
+
+impl CallSpan {
+    pub(crate) fn end(mut self, outcome: CallOutcome) {
+        self.finish(outcome);
+    }
+
+    fn finish(&mut self, outcome: CallOutcome) {
+        if let Some((metrics, proc, started)) = self.0.take() {
+            metrics.on_call_end(&proc, started.elapsed(), outcome);
+        }
+    }
+}
+
+impl Drop for CallSpan {
+    fn drop(&mut self) {
+        self.finish(CallOutcome::Cancelled);
+    }
+}
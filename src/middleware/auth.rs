@@ -0,0 +1,50 @@
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::types::{ctx::ZRpcContext, req::ZRpcReq};
+
+use super::{Middleware, error::MiddlewareError};
+
+/// Rejects requests whose auth key, set with `ZRpcClientBuilder::auth_key`,
+/// is missing or unknown, with `ProcedureError::Unauthorized`. Several keys
+/// may be accepted at once, so a new key can be rolled out before the old
+/// one is retired. Keys are compared in constant time, so the time taken
+/// doesn't tell how much of a key was right.
+pub struct AuthMiddleware {
+    keys: Vec<String>,
+}
+
+impl AuthMiddleware {
+    pub fn new(key: &str) -> Self {
+        Self {
+            keys: vec![key.to_string()],
+        }
+    }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.keys.push(key.to_string());
+        self
+    }
+}
+
+impl Middleware for AuthMiddleware {
+    fn before_call(
+        &self,
+        req: &mut ZRpcReq,
+        _ctx: &mut ZRpcContext,
+    ) -> Result<(), MiddlewareError> {
+        let Some(key) = &req.auth_key else {
+            return Err(MiddlewareError::Unauthorized);
+        };
+
+        // Every key is compared, so the time taken doesn't tell which matched.
+        let known = self.keys.iter().fold(Choice::from(0), |known, expected| {
+            known | expected.as_bytes().ct_eq(key.as_bytes())
+        });
+
+        if bool::from(known) {
+            Ok(())
+        } else {
+            Err(MiddlewareError::Unauthorized)
+        }
+    }
+}
@@ -1,11 +1,20 @@
+use std::time::Duration;
+
 #[macro_export]
 macro_rules! middleware_err {
     ($m:expr) => {{
-        use libzrpc::middleware::error::MiddlewareError;
+        use $crate::middleware::error::MiddlewareError;
 
-        Err(MiddlewareError($m.to_string()))
+        Err(MiddlewareError::Rejected($m.to_string()))
     }};
 }
 
 #[derive(Debug)]
-pub struct MiddlewareError(pub String);
+pub enum MiddlewareError {
+    Rejected(String),
+    /// Reaches the client as `ProcedureError::Unauthorized`.
+    Unauthorized,
+    /// Reaches the client as `ZRpcError::RateLimited`, suggesting when to
+    /// try again.
+    RateLimited(Duration),
+}
@@ -0,0 +1,123 @@
+use std::{collections::HashSet, fmt::Write};
+
+pub use log::Level;
+
+use crate::types::{ctx::ZRpcContext, dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq};
+
+use super::{Middleware, error::MiddlewareError};
+
+const REDACTED: &str = "***";
+
+/// Logs every call through the `log` facade once it has been answered, with
+/// its procedure, parameters, headers, duration and outcome. Parameters at
+/// the positions given to `redact_param` and headers given to
+/// `redact_header` are logged as `***`, and auth keys are never logged.
+///
+/// Added first, it also sees calls that later middleware rejects. Stream
+/// procedures are logged once their stream has ended.
+pub struct LoggingMiddleware {
+    level: Level,
+    params: HashSet<usize>,
+    headers: HashSet<String>,
+}
+
+impl LoggingMiddleware {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            params: HashSet::new(),
+            headers: HashSet::new(),
+        }
+    }
+
+    /// Hides the parameter at `index`, counting from zero, of every call.
+    pub fn redact_param(mut self, index: usize) -> Self {
+        self.params.insert(index);
+        self
+    }
+
+    /// Hides the value of the header `key`, compared case-insensitively.
+    pub fn redact_header(mut self, key: &str) -> Self {
+        self.headers.insert(key.to_ascii_lowercase());
+        self
+    }
+
+    fn params(&self, params: &[ZRpcDt]) -> String {
+        let mut out = String::new();
+
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+
+            let _ = match param {
+                _ if self.params.contains(&i) => write!(out, "{}", REDACTED),
+                // Blobs can be large and are rarely readable.
+                ZRpcDt::Bytes(bytes) | ZRpcDt::Serialized(bytes) => {
+                    write!(out, "{}({} bytes)", param.kind(), bytes.len())
+                }
+                _ => write!(out, "{:?}", param),
+            };
+        }
+
+        out
+    }
+
+    fn headers(&self, req: &ZRpcReq) -> String {
+        let mut headers: Vec<_> = req
+            .headers
+            .iter()
+            .map(|(key, value)| {
+                if self.headers.contains(&key.to_ascii_lowercase()) {
+                    format!("{}: {}", key, REDACTED)
+                } else {
+                    format!("{}: {}", key, value)
+                }
+            })
+            .collect();
+        headers.sort();
+
+        headers.join(", ")
+    }
+}
+
+impl Middleware for LoggingMiddleware {
+    fn after_call(
+        &self,
+        req: &ZRpcReq,
+        ctx: &ZRpcContext,
+        res: &mut Result<ZRpcDt, ProcedureError>,
+    ) -> Result<(), MiddlewareError> {
+        if !log::log_enabled!(self.level) {
+            return Ok(());
+        }
+
+        let outcome = match res {
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("failed: {}", e),
+        };
+
+        if req.headers.is_empty() {
+            log::log!(
+                self.level,
+                "{}({}) {} in {:?}",
+                req.proc,
+                self.params(&req.params),
+                outcome,
+                ctx.elapsed()
+            );
+        } else {
+            log::log!(
+                self.level,
+                "{}({}) [{}] {} in {:?}",
+                req.proc,
+                self.params(&req.params),
+                self.headers(req),
+                outcome,
+                ctx.elapsed()
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,109 @@
 use error::MiddlewareError;
 
-use crate::types::req::ZRpcReq;
+use crate::types::{ctx::ZRpcContext, dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq};
 
+pub mod auth;
 pub mod error;
+pub mod logging;
+pub mod rate_limit;
 
 pub trait Middleware: Send + Sync {
-    fn before_call(&self, req: &ZRpcReq) -> Result<(), MiddlewareError>;
+    /// Runs before the procedure. Middleware may rewrite the request or
+    /// attach values to the context, which the procedure can then read.
+    fn before_call(
+        &self,
+        _req: &mut ZRpcReq,
+        _ctx: &mut ZRpcContext,
+    ) -> Result<(), MiddlewareError> {
+        Ok(())
+    }
+
+    /// Runs after the procedure with its result, which may be rewritten
+    /// before it is sent back. Returning an error replaces the result with
+    /// `ProcedureError::AfterMiddleware`. For a stream procedure it runs once
+    /// the stream has ended, with `Ok(ZRpcDt::Null)` unless it failed.
+    fn after_call(
+        &self,
+        _req: &ZRpcReq,
+        _ctx: &ZRpcContext,
+        _res: &mut Result<ZRpcDt, ProcedureError>,
+    ) -> Result<(), MiddlewareError> {
+        Ok(())
+    }
+}
+
+/// Runs `before_call` hooks in the order middleware was pushed, stopping at
+/// the first one that rejects the call, then `after_call` hooks in reverse
+/// order for every middleware whose `before_call` succeeded.
+#[derive(Default)]
+pub struct MiddlewareStack {
+    middleware: Vec<Box<dyn Middleware>>,
+}
+
+impl MiddlewareStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.middleware.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middleware.is_empty()
+    }
+
+    pub(crate) fn enter(
+        &self,
+        req: &mut ZRpcReq,
+        ctx: &mut ZRpcContext,
+    ) -> (usize, Result<(), MiddlewareError>) {
+        for (i, m) in self.middleware.iter().enumerate() {
+            if let Err(e) = m.before_call(req, ctx) {
+                return (i, Err(e));
+            }
+        }
+
+        (self.middleware.len(), Ok(()))
+    }
+
+    pub(crate) fn leave(
+        &self,
+        entered: usize,
+        req: &ZRpcReq,
+        ctx: &ZRpcContext,
+        res: &mut Result<ZRpcDt, ProcedureError>,
+    ) {
+        for m in self.middleware[..entered].iter().rev() {
+            if let Err(e) = m.after_call(req, ctx, res) {
+                *res = Err(match e {
+                    MiddlewareError::Rejected(e) => ProcedureError::AfterMiddleware(e),
+                    MiddlewareError::Unauthorized => ProcedureError::Unauthorized,
+                    MiddlewareError::RateLimited(after) => ProcedureError::RateLimited(after),
+                });
+            }
+        }
+    }
+}
+
+impl Middleware for MiddlewareStack {
+    fn before_call(&self, req: &mut ZRpcReq, ctx: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        self.enter(req, ctx).1
+    }
+
+    fn after_call(
+        &self,
+        req: &ZRpcReq,
+        ctx: &ZRpcContext,
+        res: &mut Result<ZRpcDt, ProcedureError>,
+    ) -> Result<(), MiddlewareError> {
+        self.leave(self.middleware.len(), req, ctx, res);
+
+        Ok(())
+    }
 }
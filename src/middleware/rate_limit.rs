@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::types::{ctx::ZRpcContext, req::ZRpcReq};
+
+use super::{Middleware, error::MiddlewareError};
+
+type KeyFn = dyn Fn(&ZRpcReq, &ZRpcContext) -> Option<String> + Send + Sync;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    /// Full buckets are forgotten once there are this many.
+    prune_at: usize,
+}
+
+/// Limits how many calls each client may make with a token bucket: a client
+/// may make `burst` calls at once, then `rate` calls per second. Calls over
+/// the limit are rejected before reaching the procedure, and the client sees
+/// `ZRpcError::RateLimited` with how long to wait for the next token.
+///
+/// Clients are told apart by their IP address unless `key_by` says
+/// otherwise. Calls without a key, e.g. over in-memory connections, aren't
+/// limited.
+pub struct RateLimitMiddleware {
+    rate: f64,
+    burst: f64,
+    key: Box<KeyFn>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimitMiddleware {
+    const PRUNE_AT: usize = 1024;
+
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate: rate.max(0.0),
+            burst: burst.max(1) as f64,
+            key: Box::new(|_, ctx| ctx.peer_addr().map(|addr| addr.ip().to_string())),
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                prune_at: Self::PRUNE_AT,
+            }),
+        }
+    }
+
+    /// Chooses the bucket of each call, e.g. by a tenant header rather than
+    /// by address. Calls it returns `None` for aren't limited.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&ZRpcReq, &ZRpcContext) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Takes a token from the bucket of `key`, or says how long until the
+    /// next one.
+    fn take(&self, key: String) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        self.prune(&mut buckets, now);
+
+        let bucket = buckets.buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        Err(Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.rate).unwrap_or(Duration::MAX))
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate).min(self.burst)
+    }
+
+    /// Forgets full buckets once there are many, since they are the same as
+    /// new ones.
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        if buckets.buckets.len() < buckets.prune_at {
+            return;
+        }
+
+        buckets
+            .buckets
+            .retain(|_, bucket| self.refill(bucket, now) < self.burst);
+        buckets.prune_at = (buckets.buckets.len() * 2).max(Self::PRUNE_AT);
+    }
+}
+
+impl Middleware for RateLimitMiddleware {
+    fn before_call(&self, req: &mut ZRpcReq, ctx: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        match (self.key)(req, ctx) {
+            Some(key) => self.take(key).map_err(MiddlewareError::RateLimited),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,18 +1,66 @@
-use std::{collections::HashMap, net::Ipv4Addr, sync::Arc};
+use std::{
+    any::Any,
+    collections::HashMap,
+    net::SocketAddr,
+    panic::AssertUnwindSafe,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::path::Path;
+
+#[cfg(feature = "tls")]
+use tokio_rustls::TlsAcceptor;
 
+use futures::{FutureExt, Stream, StreamExt, future::BoxFuture, stream::BoxStream};
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::{
     net::{TcpListener, TcpStream},
-    sync::Mutex,
+    sync::{Mutex, OwnedSemaphorePermit, Semaphore, mpsc, watch},
+    task::JoinSet,
 };
 
+#[cfg(unix)]
+use crate::transport::unix::UnixSocket;
 use crate::{
     error::ZRpcError,
     log,
-    middleware::Middleware,
-    transport::tcp::TcpTransport,
-    types::{dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq},
+    metrics::{CallOutcome, Metrics, Observer},
+    middleware::{Middleware, MiddlewareStack},
+    transport::{
+        AsyncStream,
+        codec::{self, Codec, ZRpcCodec},
+        compression::{Compression, ZRpcCompression},
+        handshake::{Hello, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        tcp::{TcpTransport, TcpWriter},
+    },
+    types::{
+        ctx::{Peer, ZRpcContext},
+        dt::ZRpcDt,
+        frame::{ZRpcReqFrame, ZRpcResFrame},
+        proc_error::ProcedureError,
+        req::{ZRpcHeaders, ZRpcReq},
+        typed::{FromZRpcParams, IntoProcResult, TypedProc, parse_params},
+    },
+};
+#[cfg(feature = "tls")]
+use crate::{
+    transport::tls::{self, rustls::ServerConfig},
+    types::ctx::ZRpcPeerIdentity,
 };
 
+use queue::{Outgoing, Responses};
+use service::ZRpcService;
+use upload::{UPLOAD_WINDOW, UploadItem, ZRpcUpload};
+
+mod queue;
+pub mod service;
+pub mod upload;
+
 #[macro_export]
 macro_rules! add_procs {
     ($server:expr, $($proc:ident),*) => {
@@ -24,117 +72,1280 @@ macro_rules! add_procs {
     };
 }
 
+type ProcFuture = BoxFuture<'static, Result<ZRpcDt, ProcedureError>>;
+type Proc = Arc<dyn Fn(&ZRpcContext, &Vec<ZRpcDt>) -> ProcFuture + Send + Sync>;
+type Procs = Arc<Mutex<HashMap<String, Proc>>>;
+
+type ProcStream = BoxStream<'static, Result<ZRpcDt, ProcedureError>>;
+type StreamProc =
+    Arc<dyn Fn(&ZRpcContext, &Vec<ZRpcDt>) -> Result<ProcStream, ProcedureError> + Send + Sync>;
+type StreamProcs = Arc<Mutex<HashMap<String, StreamProc>>>;
+
+type UploadProc = Arc<dyn Fn(&ZRpcContext, &Vec<ZRpcDt>, ZRpcUpload) -> ProcFuture + Send + Sync>;
+type UploadProcs = Arc<Mutex<HashMap<String, UploadProc>>>;
+
+/// Answers calls to procedures that aren't registered, given the name called.
+type Fallback = Arc<dyn Fn(&ZRpcContext, &str, &Vec<ZRpcDt>) -> ProcFuture + Send + Sync>;
+
+type Middlewares = Arc<Mutex<MiddlewareStack>>;
+
+fn typed_proc<Args, R>(name: &str, proc: impl TypedProc<Args, R>) -> Proc
+where
+    Args: FromZRpcParams,
+    R: IntoProcResult,
+{
+    let proc_name = name.to_string();
+
+    sync_proc(move |_, params| {
+        let args = parse_params::<Args>(&proc_name, params)?;
+
+        proc.call(args).into_proc_result()
+    })
+}
+
+/// A procedure that answers before returning, as a future that is already
+/// complete.
+fn sync_proc<F>(proc: F) -> Proc
+where
+    F: Fn(&ZRpcContext, &Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + 'static + Send + Sync,
+{
+    Arc::new(move |ctx, params| futures::future::ready(proc(ctx, params)).boxed())
+}
+
+fn async_proc<F, Fut>(proc: F) -> Proc
+where
+    F: Fn(ZRpcContext, Vec<ZRpcDt>) -> Fut + 'static + Send + Sync,
+    Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+{
+    Arc::new(move |ctx, params| proc(ctx.clone(), params.clone()).boxed())
+}
+
+fn stream_proc<F, S>(proc: F) -> StreamProc
+where
+    F: Fn(&Vec<ZRpcDt>) -> Result<S, ProcedureError> + 'static + Send + Sync,
+    S: Stream<Item = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+{
+    Arc::new(move |_, params| proc(params).map(StreamExt::boxed))
+}
+
+fn upload_proc<F, Fut>(proc: F) -> UploadProc
+where
+    F: Fn(ZRpcContext, Vec<ZRpcDt>, ZRpcUpload) -> Fut + 'static + Send + Sync,
+    Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+{
+    Arc::new(move |ctx, params, upload| proc(ctx.clone(), params.clone(), upload).boxed())
+}
+
+type Limits = Arc<Mutex<HashMap<String, Arc<ConcurrencyLimit>>>>;
+
+/// Everything a connection needs to dispatch calls, shared by all of them.
+#[derive(Clone)]
+struct Registry {
+    procs: Procs,
+    stream_procs: StreamProcs,
+    upload_procs: UploadProcs,
+    fallback: Arc<Mutex<Option<Fallback>>>,
+    middleware: Middlewares,
+    limits: Limits,
+    metrics: Observer,
+}
+
+struct ConcurrencyLimit {
+    permits: Arc<Semaphore>,
+    max_queued: usize,
+    queued: AtomicUsize,
+}
+
+impl ConcurrencyLimit {
+    /// Waits for a free slot, unless `max_queued` calls are waiting already.
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit, ProcedureError> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::AcqRel) >= self.max_queued {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+            return Err(ProcedureError::Overloaded);
+        }
+
+        // Also leaves the queue if the call is cancelled while waiting.
+        struct Queued<'a>(&'a AtomicUsize);
+
+        impl Drop for Queued<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+
+        let _queued = Queued(&self.queued);
+
+        self.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| ProcedureError::Internal("concurrency limit is closed".to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    Draining,
+    Closing,
+}
+
+/// Handed to every connection, so it can follow the server shutting down
+/// and hold the shutdown back while one of its calls is still running.
+#[derive(Clone)]
+struct Lifecycle {
+    phase: watch::Receiver<Phase>,
+    inflight: mpsc::WeakSender<()>,
+}
+
+impl Lifecycle {
+    /// Returns a token to hold for the duration of a call, or `None` once
+    /// the server no longer takes new calls.
+    fn enter(&self) -> Option<mpsc::Sender<()>> {
+        if *self.phase.borrow() != Phase::Running {
+            return None;
+        }
+
+        self.inflight.upgrade()
+    }
+
+    async fn closing(&mut self) {
+        let _ = self.phase.wait_for(|phase| *phase == Phase::Closing).await;
+    }
+}
+
+enum Listener {
+    /// Every address the server listens on.
+    Tcp(Vec<TcpListener>),
+    #[cfg(unix)]
+    Unix(UnixSocket),
+}
+
+enum Incoming {
+    Tcp(TcpStream, SocketAddr),
+    Stream(Box<dyn AsyncStream>),
+}
+
+/// Hands connections to a server without going through its listener, see
+/// `ZRpcClient::new_in_memory`.
+#[derive(Clone)]
+pub struct ZRpcServerHandle {
+    connections: mpsc::UnboundedSender<Box<dyn AsyncStream>>,
+}
+
+impl ZRpcServerHandle {
+    const BUFFER_SIZE: usize = 64 * 1024;
+
+    /// Serves `stream` as a connection of its own, e.g. one end of a pipe
+    /// or a socket accepted elsewhere. It is never wrapped in TLS.
+    pub fn serve<S: AsyncStream + 'static>(&self, stream: S) -> Result<(), ZRpcError> {
+        self.connections.send(Box::new(stream)).map_err(|_| {
+            ZRpcError::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "server has shut down",
+            ))
+        })
+    }
+
+    pub(crate) fn connect(&self) -> Result<tokio::io::DuplexStream, ZRpcError> {
+        let (client, server) = tokio::io::duplex(Self::BUFFER_SIZE);
+        self.serve(server)?;
+
+        Ok(client)
+    }
+}
+
 pub struct ZRpcServer {
-    listener: TcpListener,
-    procs: Arc<
-        Mutex<
-            HashMap<
-                String,
-                Box<dyn Fn(&Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + Send + Sync>,
-            >,
-        >,
-    >,
-    middleware: Arc<Mutex<Vec<Box<dyn Middleware>>>>,
+    listener: Option<Listener>,
+    handle: ZRpcServerHandle,
+    memory: mpsc::UnboundedReceiver<Box<dyn AsyncStream>>,
+    registry: Registry,
+    grace_period: Duration,
+    compression: Option<ZRpcCompression>,
+    compression_threshold: usize,
+    max_frame_size: usize,
+    codec: Arc<dyn Codec>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
 }
 
 impl ZRpcServer {
-    pub async fn new(addr: (Ipv4Addr, u16)) -> Result<Self, ZRpcError> {
-        let listener = TcpListener::bind(addr).await.map_err(ZRpcError::Io)?;
+    /// Listens on `addr`, an IPv4 or IPv6 address and a port.
+    pub async fn new(addr: impl Into<SocketAddr>) -> Result<Self, ZRpcError> {
+        Self::bind(&[addr.into()]).await
+    }
+
+    /// Listens on every one of `addrs`, e.g. on several interfaces, serving
+    /// connections from all of them alike. If any of them can't be bound,
+    /// the error names it and the server listens on none of them. An IPv6
+    /// address bound along with an IPv4 one on the same port only accepts
+    /// IPv6 connections, so that both stacks can be served on that port.
+    pub async fn bind(addrs: &[SocketAddr]) -> Result<Self, ZRpcError> {
+        if addrs.is_empty() {
+            return Err(ZRpcError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no address to listen on",
+            )));
+        }
+
+        let listeners = addrs
+            .iter()
+            .map(|addr| {
+                // Port 0 picks a different port for each address.
+                let only_v6 = addr.port() != 0
+                    && addrs
+                        .iter()
+                        .any(|other| other.is_ipv4() && other.port() == addr.port());
+
+                Self::listen(*addr, only_v6).map_err(|e| {
+                    ZRpcError::Io(std::io::Error::new(
+                        e.kind(),
+                        format!("failed to listen on {}: {}", addr, e),
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
 
         Ok(Self {
-            listener,
-            procs: Arc::new(Mutex::new(HashMap::new())),
-            middleware: Arc::new(Mutex::new(vec![])),
+            listener: Some(Listener::Tcp(listeners)),
+            ..Self::new_in_memory()
         })
     }
 
+    fn listen(addr: SocketAddr, only_v6: bool) -> std::io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+        if addr.is_ipv6() {
+            socket.set_only_v6(only_v6)?;
+        }
+
+        // Like `TcpListener::bind`, so that a restarted server can bind
+        // while connections of the previous one linger.
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+
+        socket.bind(&addr.into())?;
+        socket.listen(1024)?;
+        socket.set_nonblocking(true)?;
+
+        TcpListener::from_std(socket.into())
+    }
+
+    /// The addresses the server listens on, e.g. to find the ports chosen
+    /// for port 0.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        match &self.listener {
+            Some(Listener::Tcp(listeners)) => listeners
+                .iter()
+                .filter_map(|listener| listener.local_addr().ok())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Listens on a Unix domain socket at `path`. A socket file left behind
+    /// by a server that is no longer running is replaced, and the file is
+    /// removed again when the server is dropped.
+    #[cfg(unix)]
+    pub async fn new_unix(path: impl AsRef<Path>) -> Result<Self, ZRpcError> {
+        Self::bind_unix(path.as_ref(), None).await
+    }
+
+    /// Like `new_unix`, but sets the permissions of the socket file to `mode`
+    /// (e.g. `0o660`) to control which users may connect. The socket only
+    /// appears at `path` once it has `mode`, so the directory containing it
+    /// must be writable to create a private directory next to it.
+    #[cfg(unix)]
+    pub async fn new_unix_with_mode(path: impl AsRef<Path>, mode: u32) -> Result<Self, ZRpcError> {
+        Self::bind_unix(path.as_ref(), Some(mode)).await
+    }
+
+    #[cfg(unix)]
+    async fn bind_unix(path: &Path, mode: Option<u32>) -> Result<Self, ZRpcError> {
+        let socket = UnixSocket::bind(path, mode).await.map_err(ZRpcError::Io)?;
+
+        Ok(Self {
+            listener: Some(Listener::Unix(socket)),
+            ..Self::new_in_memory()
+        })
+    }
+
+    /// A server that only accepts connections opened through its `handle`,
+    /// e.g. to test clients without binding a port.
+    pub fn new_in_memory() -> Self {
+        let (connections, memory) = mpsc::unbounded_channel();
+
+        Self {
+            listener: None,
+            handle: ZRpcServerHandle { connections },
+            memory,
+            registry: Registry {
+                procs: Arc::new(Mutex::new(HashMap::new())),
+                stream_procs: Arc::new(Mutex::new(HashMap::new())),
+                upload_procs: Arc::new(Mutex::new(HashMap::new())),
+                fallback: Arc::new(Mutex::new(None)),
+                middleware: Arc::new(Mutex::new(MiddlewareStack::new())),
+                limits: Arc::new(Mutex::new(HashMap::new())),
+                metrics: Observer::default(),
+            },
+            grace_period: Duration::from_secs(30),
+            compression: None,
+            compression_threshold: Compression::DEFAULT_THRESHOLD,
+            max_frame_size: TcpTransport::DEFAULT_MAX_FRAME_SIZE,
+            codec: Arc::new(ZRpcCodec::default()),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub async fn new_tls(
+        addr: impl Into<SocketAddr>,
+        config: Arc<ServerConfig>,
+    ) -> Result<Self, ZRpcError> {
+        Self::bind_tls(&[addr.into()], config).await
+    }
+
+    /// Like `bind`, encrypting every connection with TLS.
+    #[cfg(feature = "tls")]
+    pub async fn bind_tls(
+        addrs: &[SocketAddr],
+        config: Arc<ServerConfig>,
+    ) -> Result<Self, ZRpcError> {
+        let mut server = Self::bind(addrs).await?;
+        server.tls = Some(config.into());
+
+        Ok(server)
+    }
+
     pub async fn start(&mut self) -> Result<(), ZRpcError> {
-        while let Ok((stream, _)) = self.listener.accept().await {
-            let procs = self.procs.clone();
-            let middleware = self.middleware.clone();
+        self.start_with_shutdown(std::future::pending()).await
+    }
+
+    /// Like `start`, but once `signal` completes the server stops accepting
+    /// connections and answers new calls with `ProcedureError::ShuttingDown`.
+    /// Calls already running get up to the grace period to finish and send
+    /// their response, then the remaining connections are closed.
+    pub async fn start_with_shutdown(
+        &mut self,
+        signal: impl Future<Output = ()>,
+    ) -> Result<(), ZRpcError> {
+        let (phase, _) = watch::channel(Phase::Running);
+        let (inflight, mut idle) = mpsc::channel::<()>(1);
+        let lifecycle = Lifecycle {
+            phase: phase.subscribe(),
+            inflight: inflight.downgrade(),
+        };
+
+        let mut connections = JoinSet::new();
+        tokio::pin!(signal);
+
+        loop {
+            let incoming = tokio::select! {
+                res = Self::accept(&self.listener) => match res {
+                    Ok(incoming) => incoming,
+                    Err(_) => break,
+                },
+                Some(stream) = self.memory.recv() => Incoming::Stream(stream),
+                _ = &mut signal => break,
+            };
+
+            while connections.try_join_next().is_some() {}
+
+            let registry = self.registry.clone();
+            let compression = self.compression.map(|algorithm| Compression {
+                algorithm,
+                threshold: self.compression_threshold,
+            });
+            let max_frame_size = self.max_frame_size;
+            let codec = self.codec.clone();
+            #[allow(unused_mut)]
+            let mut lifecycle = lifecycle.clone();
+            #[cfg(feature = "tls")]
+            let tls = self.tls.clone();
 
-            tokio::spawn(async move {
-                if let Err(e) = Self::handle_stream(stream, &procs, &middleware).await {
-                    eprintln!("Failed to handle stream: {}", e);
+            connections.spawn(async move {
+                let _connection = registry.metrics.connection();
+
+                let mut peer = Peer::default();
+
+                let mut transport = match incoming {
+                    #[cfg(feature = "tls")]
+                    Incoming::Tcp(stream, addr) => {
+                        peer.addr = Some(addr);
+
+                        match Self::accept_tls(stream, tls, &mut lifecycle).await {
+                            Some((transport, identity)) => {
+                                peer.identity = identity.map(Arc::new);
+                                transport
+                            }
+                            None => return,
+                        }
+                    }
+                    #[cfg(not(feature = "tls"))]
+                    Incoming::Tcp(stream, addr) => {
+                        peer.addr = Some(addr);
+                        TcpTransport::new(stream)
+                    }
+                    // Unix and in-memory connections are never encrypted.
+                    Incoming::Stream(stream) => TcpTransport::new(stream),
+                };
+                transport.set_max_frame_size(max_frame_size);
+                transport.set_codec(codec);
+
+                if let Err(e) =
+                    Self::handle_stream(transport, &registry, lifecycle, compression, peer).await
+                {
+                    log!("[ZRpcServer] Failed to handle connection: {}", e);
                 }
             });
         }
 
+        log!(
+            "[ZRpcServer] Shutting down, waiting up to {:?} for running calls",
+            self.grace_period
+        );
+
+        phase.send_replace(Phase::Draining);
+        drop(inflight);
+
+        self.memory.close();
+        while self.memory.try_recv().is_ok() {}
+
+        if tokio::time::timeout(self.grace_period, idle.recv())
+            .await
+            .is_err()
+        {
+            log!(
+                "[ZRpcServer] Grace period of {:?} elapsed, aborting running calls",
+                self.grace_period
+            );
+        }
+
+        phase.send_replace(Phase::Closing);
+        while connections.join_next().await.is_some() {}
+
         Ok(())
     }
 
+    /// Connections opened through the handle are served like those from the
+    /// listener, in addition to them.
+    pub fn handle(&self) -> ZRpcServerHandle {
+        self.handle.clone()
+    }
+
+    /// Compresses responses of at least the compression threshold, for
+    /// clients that support `algorithm`. Off by default.
+    pub fn set_compression(&mut self, algorithm: ZRpcCompression) {
+        self.compression = Some(algorithm);
+    }
+
+    /// Defaults to 1 KiB.
+    pub fn set_compression_threshold(&mut self, threshold: usize) {
+        self.compression_threshold = threshold;
+    }
+
+    /// The largest request frame, in bytes, the server accepts. A client
+    /// sending a larger one is disconnected. Defaults to 16 MiB.
+    pub fn set_max_frame_size(&mut self, max: usize) {
+        self.max_frame_size = max;
+    }
+
+    /// The codec frames are encoded with, which clients have to use as well.
+    /// A client using another one is disconnected after its first frame.
+    /// Defaults to `ZRpcCodec::Bincode`.
+    pub fn set_codec(&mut self, codec: impl Codec + 'static) {
+        self.codec = Arc::new(codec);
+    }
+
+    /// How long `start_with_shutdown` waits for running calls once the
+    /// shutdown signal fires. Defaults to 30 seconds.
+    pub fn set_grace_period(&mut self, grace_period: Duration) {
+        self.grace_period = grace_period;
+    }
+
     pub async fn add_proc<F>(&mut self, name: &str, proc: F)
     where
         F: Fn(&Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + 'static + Send + Sync,
+    {
+        self.add_proc_with_ctx(name, move |_, params| proc(params))
+            .await;
+    }
+
+    /// Like `add_proc`, but the procedure also receives the context that
+    /// middleware filled in for this call.
+    pub async fn add_proc_with_ctx<F>(&mut self, name: &str, proc: F)
+    where
+        F: Fn(&ZRpcContext, &Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + 'static + Send + Sync,
     {
         log!("[ZRpcServer] '{}' procedure has been loaded", name);
 
-        self.procs
+        self.registry
+            .procs
             .lock()
             .await
-            .insert(name.to_string(), Box::new(proc));
+            .insert(name.to_string(), sync_proc(proc));
+    }
+
+    /// Registers a procedure that runs asynchronously, receiving its own
+    /// copy of the call's context and parameters. The context's `cancelled`
+    /// future resolves when the client disconnects or the call's deadline
+    /// passes, so long-running work can stop early.
+    pub async fn add_async_proc<F, Fut>(&mut self, name: &str, proc: F)
+    where
+        F: Fn(ZRpcContext, Vec<ZRpcDt>) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        log!("[ZRpcServer] '{}' procedure has been loaded", name);
+
+        self.registry
+            .procs
+            .lock()
+            .await
+            .insert(name.to_string(), async_proc(proc));
+    }
+
+    pub async fn add_typed_proc<Args, R>(&mut self, name: &str, proc: impl TypedProc<Args, R>)
+    where
+        Args: FromZRpcParams,
+        R: IntoProcResult,
+    {
+        log!("[ZRpcServer] '{}' procedure has been loaded", name);
+
+        self.registry
+            .procs
+            .lock()
+            .await
+            .insert(name.to_string(), typed_proc(name, proc));
+    }
+
+    /// Registers a procedure answering `ZRpcClient::call_stream`. The returned
+    /// stream is only polled while the client has granted credit for more
+    /// chunks, and is dropped as soon as the client cancels.
+    pub async fn add_stream_proc<F, S>(&mut self, name: &str, proc: F)
+    where
+        F: Fn(&Vec<ZRpcDt>) -> Result<S, ProcedureError> + 'static + Send + Sync,
+        S: Stream<Item = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        log!("[ZRpcServer] '{}' stream procedure has been loaded", name);
+
+        self.registry
+            .stream_procs
+            .lock()
+            .await
+            .insert(name.to_string(), stream_proc(proc));
+    }
+
+    /// Registers a procedure answering `ZRpcClient::call_upload`, which reads
+    /// the uploaded data from its `ZRpcUpload` as it arrives. The client only
+    /// sends a few chunks ahead of the procedure, so the data is never held
+    /// in memory all at once. The answer is sent once the procedure returns,
+    /// whether or not it read all of the data.
+    pub async fn add_upload_proc<F, Fut>(&mut self, name: &str, proc: F)
+    where
+        F: Fn(ZRpcContext, Vec<ZRpcDt>, ZRpcUpload) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        log!("[ZRpcServer] '{}' upload procedure has been loaded", name);
+
+        self.registry
+            .upload_procs
+            .lock()
+            .await
+            .insert(name.to_string(), upload_proc(proc));
+    }
+
+    /// Mounts the procedures of `service` as `prefix.name`.
+    pub async fn add_service(&mut self, prefix: &str, service: impl Into<ZRpcService>) {
+        let service = service.into();
+        let mut procs = self.registry.procs.lock().await;
+        for (name, proc) in service.procs {
+            let name = format!("{}.{}", prefix, name);
+            log!("[ZRpcServer] '{}' procedure has been loaded", name);
+            procs.insert(name, proc);
+        }
+        drop(procs);
+
+        let mut stream_procs = self.registry.stream_procs.lock().await;
+        for (name, proc) in service.stream_procs {
+            let name = format!("{}.{}", prefix, name);
+            log!("[ZRpcServer] '{}' stream procedure has been loaded", name);
+            stream_procs.insert(name, proc);
+        }
+        drop(stream_procs);
+
+        let mut upload_procs = self.registry.upload_procs.lock().await;
+        for (name, proc) in service.upload_procs {
+            let name = format!("{}.{}", prefix, name);
+            log!("[ZRpcServer] '{}' upload procedure has been loaded", name);
+            upload_procs.insert(name, proc);
+        }
+    }
+
+    /// Answers calls to procedures that aren't registered, e.g. to forward
+    /// them elsewhere, instead of failing them with `ProcedureError::MethodNotFound`.
+    /// The handler receives the name that was called along with the context
+    /// and parameters. Registered procedures always take precedence.
+    pub async fn set_fallback<F, Fut>(&mut self, fallback: F)
+    where
+        F: Fn(ZRpcContext, String, Vec<ZRpcDt>) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        *self.registry.fallback.lock().await = Some(Arc::new(move |ctx, name, params| {
+            fallback(ctx.clone(), name.to_string(), params.clone()).boxed()
+        }));
+    }
+
+    /// Runs at most `limit` calls of the procedure `name` at once. Further
+    /// calls wait for a free slot, and once `max_queued` of them are waiting
+    /// the rest fail, reaching the client as `ZRpcError::Overloaded`. Other
+    /// procedures are not affected.
+    pub async fn set_max_concurrency(&mut self, name: &str, limit: usize, max_queued: usize) {
+        self.registry.limits.lock().await.insert(
+            name.to_string(),
+            Arc::new(ConcurrencyLimit {
+                permits: Arc::new(Semaphore::new(limit)),
+                max_queued,
+                queued: AtomicUsize::new(0),
+            }),
+        );
     }
 
     pub async fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
-        self.middleware.lock().await.push(Box::new(middleware));
+        self.registry.middleware.lock().await.push(middleware);
     }
 
-    async fn handle_stream(
+    /// Reports connections and calls to `metrics`, replacing any observer
+    /// set before. Calls turned away during shutdown are not reported.
+    pub fn set_metrics<M: Metrics + 'static>(&mut self, metrics: M) {
+        self.registry.metrics = Observer::new(metrics);
+    }
+
+    async fn accept(listener: &Option<Listener>) -> std::io::Result<Incoming> {
+        match listener {
+            Some(Listener::Tcp(listeners)) => {
+                let accepts = listeners.iter().map(|listener| Box::pin(listener.accept()));
+                let (res, _, _) = futures::future::select_all(accepts).await;
+
+                res.map(|(stream, addr)| Incoming::Tcp(stream, addr))
+            }
+            #[cfg(unix)]
+            Some(Listener::Unix(socket)) => socket
+                .listener
+                .accept()
+                .await
+                .map(|(stream, _)| Incoming::Stream(Box::new(stream))),
+            None => std::future::pending().await,
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    async fn accept_tls(
         stream: TcpStream,
-        procs: &Arc<
-            Mutex<
-                HashMap<
-                    String,
-                    Box<dyn Fn(&Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + Send + Sync>,
-                >,
-            >,
-        >,
-        middleware: &Arc<Mutex<Vec<Box<dyn Middleware>>>>,
+        tls: Option<TlsAcceptor>,
+        lifecycle: &mut Lifecycle,
+    ) -> Option<(TcpTransport, Option<ZRpcPeerIdentity>)> {
+        let Some(acceptor) = tls else {
+            return Some((TcpTransport::new(stream), None));
+        };
+
+        tokio::select! {
+            res = acceptor.accept(stream) => match res {
+                Ok(stream) => {
+                    let identity = tls::peer_identity(stream.get_ref().1);
+                    Some((TcpTransport::new(stream), identity))
+                }
+                Err(e) => {
+                    log!("[ZRpcServer] TLS handshake failed: {}", e);
+                    None
+                }
+            },
+            _ = lifecycle.closing() => None,
+        }
+    }
+
+    /// What to answer a client's handshake with, refusing clients this
+    /// server can't talk to.
+    fn answer_hello(hello: &Hello, codec: &dyn Codec) -> Hello {
+        if !hello.codecs.contains(&codec.tag()) {
+            return Hello::refuse(
+                codec,
+                format!(
+                    "the client ({}) can't encode frames with {}, as this server does",
+                    hello.version,
+                    codec::describe(codec.tag())
+                ),
+            );
+        }
+
+        if hello.protocol < MIN_PROTOCOL_VERSION {
+            return Hello::refuse(
+                codec,
+                format!(
+                    "the client ({}) speaks protocol {}, this server ({}) speaks {} to {}",
+                    hello.version,
+                    hello.protocol,
+                    env!("CARGO_PKG_VERSION"),
+                    MIN_PROTOCOL_VERSION,
+                    PROTOCOL_VERSION
+                ),
+            );
+        }
+
+        // Newer clients are answered with the newest version both speak.
+        Hello::new(hello.protocol.min(PROTOCOL_VERSION), codec)
+    }
+
+    /// Serves one connection. Every call runs in its own task and answers
+    /// through a shared writer task, so a slow procedure doesn't hold up the
+    /// other calls multiplexed on the connection.
+    async fn handle_stream(
+        mut transport: TcpTransport,
+        registry: &Registry,
+        mut lifecycle: Lifecycle,
+        compression: Option<Compression>,
+        peer: Peer,
     ) -> Result<(), ZRpcError> {
-        let mut transport = TcpTransport::new(stream);
+        let hello = tokio::select! {
+            hello = transport.receive_hello() => hello?,
+            _ = lifecycle.closing() => return Ok(()),
+        };
 
-        loop {
-            let buf = match transport.receive().await {
-                Ok(buf) => buf,
+        let answer = Self::answer_hello(&hello, &*transport.codec());
+        transport.send_hello(&answer).await?;
+
+        if let Some(e) = answer.error {
+            return Err(ZRpcError::IncompatibleProtocol(e));
+        }
+
+        transport.set_compression(compression.and_then(|c| c.negotiate(&hello.compression())));
+
+        let (mut reader, writer) = transport.into_split();
+        let (frames, queue) = Responses::new();
+        let writer = tokio::spawn(Self::write_frames(writer, queue));
+
+        // Credit for the open streams; dropping a sender cancels its stream.
+        let mut streams: HashMap<u32, mpsc::UnboundedSender<u32>> = HashMap::new();
+        // Data for the open uploads; dropping a sender aborts its upload.
+        let mut uploads: HashMap<u32, mpsc::Sender<UploadItem>> = HashMap::new();
+        let mut calls = JoinSet::new();
+        // Nobody waits for notifications, so they outlive the connection.
+        let mut notifications = JoinSet::new();
+        // Tells the contexts of running calls that nobody waits for them.
+        let (closed, _) = watch::channel(false);
+        let (detached, _) = watch::channel(false);
+
+        let res = loop {
+            // Requests are only read while their answers have room, so a
+            // client that doesn't read is slowed down in turn.
+            let frame = tokio::select! {
+                frame = async {
+                    frames.ready().await;
+                    reader.receive_frame().await
+                } => frame,
+                _ = lifecycle.closing() => break Ok(()),
+            };
+
+            let frame = match frame {
+                Ok(frame) => frame,
                 Err(ZRpcError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                     log!(
                         "[ZRpcServer:{:?}] Connection closed",
                         std::thread::current().id()
                     );
-                    return Ok(());
+                    break Ok(());
                 }
-                Err(e) => return Err(e),
+                Err(e) => break Err(e),
             };
 
-            let req: ZRpcReq =
-                bincode::deserialize(&buf).map_err(|e| ZRpcError::Serialization(e.to_string()))?;
+            while calls.try_join_next().is_some() {}
+            while notifications.try_join_next().is_some() {}
+
+            match frame {
+                ZRpcReqFrame::Call(id, req) => {
+                    let Some(inflight) = lifecycle.enter() else {
+                        let _ = frames
+                            .send(ZRpcResFrame::Result(
+                                id,
+                                Err(ProcedureError::ShuttingDown),
+                                ZRpcHeaders::new(),
+                            ))
+                            .await;
+                        continue;
+                    };
+
+                    let registry = registry.clone();
+                    let frames = frames.clone();
 
-            let res = {
-                let middleware_lock = middleware.lock().await;
-                let res = middleware_lock
-                    .iter()
-                    .try_fold((), |_, m| m.before_call(&req));
-                drop(middleware_lock);
+                    let ctx = Self::context(&req, &closed, &peer);
 
-                match res {
-                    Ok(_) => match procs.lock().await.get(&req.0) {
-                        Some(proc) => proc(&req.1),
-                        None => Err(ProcedureError::NotFound),
-                    },
-                    Err(e) => Err(e.into()),
+                    calls.spawn(async move {
+                        let _inflight = inflight;
+
+                        let res = Self::call_proc(req, ctx.clone(), &registry).await;
+                        let _ = frames.send(Self::reply(id, res, &ctx)).await;
+                    });
                 }
-            };
+                ZRpcReqFrame::Stream(id, mut req, credit) => {
+                    let Some(inflight) = lifecycle.enter() else {
+                        let _ = frames
+                            .send(ZRpcResFrame::End(id, Err(ProcedureError::ShuttingDown)))
+                            .await;
+                        continue;
+                    };
+
+                    let registry = registry.clone();
+                    let frames = frames.clone();
+
+                    let (control, credits) = mpsc::unbounded_channel();
+                    streams.retain(|_, control| !control.is_closed());
+                    streams.insert(id, control);
+                    let mut ctx = Self::context(&req, &closed, &peer);
+
+                    calls.spawn(async move {
+                        let _inflight = inflight;
+                        let span = registry.metrics.call(&req.proc);
+
+                        // The middleware is released before the call waits
+                        // for its turn, as other calls need it meanwhile.
+                        let (entered, res) =
+                            registry.middleware.lock().await.enter(&mut req, &mut ctx);
+
+                        let stream = match res {
+                            Ok(_) => {
+                                ctx.set_headers(req.headers.clone());
+
+                                match Self::acquire(&req.proc, &registry.limits).await {
+                                    Ok(permit) => Self::unwind(
+                                        &req.proc,
+                                        Self::open_stream(&req, &ctx, &registry),
+                                    )
+                                    .await
+                                    .map(|stream| (Self::unwind_stream(&req.proc, stream), permit)),
+                                    Err(e) => Err(e),
+                                }
+                            }
+                            Err(e) => Err(e.into()),
+                        };
+
+                        let (mut res, cancelled) = match stream {
+                            // The slot stays taken until the stream ends.
+                            Ok((stream, _permit)) => {
+                                Self::serve_stream(id, stream, credit, credits, &frames).await
+                            }
+                            Err(e) => (Err(e), false),
+                        };
+
+                        registry
+                            .middleware
+                            .lock()
+                            .await
+                            .leave(entered, &req, &ctx, &mut res);
+
+                        span.end(if cancelled {
+                            CallOutcome::Cancelled
+                        } else {
+                            CallOutcome::of(&res)
+                        });
+                        let _ = frames.send(ZRpcResFrame::End(id, res.map(|_| ()))).await;
+                    });
+                }
+                ZRpcReqFrame::Batch(id, reqs) => {
+                    let Some(inflight) = lifecycle.enter() else {
+                        let results = reqs
+                            .iter()
+                            .map(|_| Err(ProcedureError::ShuttingDown))
+                            .collect();
+                        let _ = frames.send(ZRpcResFrame::Batch(id, results)).await;
+                        continue;
+                    };
+
+                    let registry = registry.clone();
+                    let frames = frames.clone();
+
+                    let reqs: Vec<_> = reqs
+                        .into_iter()
+                        .map(|req| (Self::context(&req, &closed, &peer), req))
+                        .collect();
+
+                    calls.spawn(async move {
+                        let _inflight = inflight;
+
+                        let results = futures::future::join_all(
+                            reqs.into_iter()
+                                .map(|(ctx, req)| Self::call_proc(req, ctx, &registry)),
+                        )
+                        .await;
+                        let _ = frames.send(ZRpcResFrame::Batch(id, results)).await;
+                    });
+                }
+                ZRpcReqFrame::Credit(id, n) => {
+                    if let Some(control) = streams.get(&id) {
+                        let _ = control.send(n);
+                    }
+                }
+                ZRpcReqFrame::Cancel(id) => {
+                    streams.remove(&id);
+                    uploads.remove(&id);
+                }
+                ZRpcReqFrame::Ping(id) => {
+                    frames.send_control(ZRpcResFrame::Pong(id));
+                }
+                ZRpcReqFrame::Notify(req) => {
+                    let Some(inflight) = lifecycle.enter() else {
+                        log!(
+                            "[ZRpcServer] Dropped '{}' notification during shutdown",
+                            req.proc
+                        );
+                        continue;
+                    };
+
+                    let registry = registry.clone();
+                    let ctx = Self::context(&req, &detached, &peer);
 
-            let bytes =
-                bincode::serialize(&res).map_err(|e| ZRpcError::Serialization(e.to_string()))?;
+                    notifications.spawn(async move {
+                        let _inflight = inflight;
+                        let proc = req.proc.clone();
 
-            transport.send(&bytes).await?;
+                        if let Err(e) = Self::call_proc(req, ctx, &registry).await {
+                            log!("[ZRpcServer] '{}' notification failed: {}", proc, e);
+                        }
+                    });
+                }
+                ZRpcReqFrame::Upload(id, req) => {
+                    let Some(inflight) = lifecycle.enter() else {
+                        let _ = frames
+                            .send(ZRpcResFrame::Result(
+                                id,
+                                Err(ProcedureError::ShuttingDown),
+                                ZRpcHeaders::new(),
+                            ))
+                            .await;
+                        continue;
+                    };
+
+                    let registry = registry.clone();
+                    let frames = frames.clone();
+
+                    // Room for a full window of data and its end.
+                    let (data, items) = mpsc::channel(UPLOAD_WINDOW as usize + 1);
+                    uploads.retain(|_, data| !data.is_closed());
+                    uploads.insert(id, data);
+                    let upload = ZRpcUpload::new(id, items, frames.clone());
+                    let ctx = Self::context(&req, &closed, &peer);
+
+                    calls.spawn(async move {
+                        let _inflight = inflight;
+
+                        let res = Self::call_upload(req, ctx.clone(), upload, &registry).await;
+                        let _ = frames.send(Self::reply(id, res, &ctx)).await;
+                    });
+                }
+                ZRpcReqFrame::Data(id, bytes) => {
+                    // Either the procedure stopped reading, or the client sent
+                    // more than its credit, which aborts the upload.
+                    if let Some(data) = uploads.get(&id)
+                        && data.try_send(UploadItem::Data(bytes)).is_err()
+                    {
+                        uploads.remove(&id);
+                    }
+                }
+                ZRpcReqFrame::UploadEnd(id) => {
+                    if let Some(data) = uploads.remove(&id) {
+                        let _ = data.try_send(UploadItem::End);
+                    }
+                }
+            }
+        };
+
+        // Calls still running when the connection closes can't be answered,
+        // but responses that are already queued are flushed.
+        closed.send_replace(true);
+        calls.shutdown().await;
+        drop(frames);
+        let _ = writer.await;
+
+        // Notifications were accepted when they were written, so they run to
+        // the end, unless the server closes before.
+        tokio::select! {
+            _ = async { while notifications.join_next().await.is_some() {} } => {}
+            _ = lifecycle.closing() => {}
+        }
+
+        res
+    }
+
+    async fn write_frames(mut writer: TcpWriter, mut queue: mpsc::UnboundedReceiver<Outgoing>) {
+        while let Some(outgoing) = queue.recv().await {
+            let frame = outgoing.frame;
+            let sent = match writer.send_frame(&frame).await {
+                Err(ZRpcError::Serialization(e)) => {
+                    log!("[ZRpcServer] Failed to encode response: {}", e);
+                    let frame = Self::unencodable(frame);
+                    writer.send_frame(&frame).await.map(|_| frame)
+                }
+                res => res.map(|_| frame),
+            };
+
+            let frame = match sent {
+                Ok(frame) => frame,
+                Err(e) => {
+                    log!("[ZRpcServer] Failed to send response: {}", e);
+                    return;
+                }
+            };
 
             log!(
                 "[ZRpcServer:{:?}] Response sent: {:?}",
                 std::thread::current().id(),
-                res
+                frame
             );
         }
     }
+
+    /// What to answer instead of a response that can't be encoded, e.g. one
+    /// nested too deeply.
+    fn unencodable(frame: ZRpcResFrame) -> ZRpcResFrame {
+        let error = || ProcedureError::Internal("the response could not be encoded".to_string());
+
+        match frame {
+            ZRpcResFrame::Result(id, _, _) => {
+                ZRpcResFrame::Result(id, Err(error()), ZRpcHeaders::new())
+            }
+            ZRpcResFrame::Chunk(id, _) | ZRpcResFrame::End(id, _) => {
+                ZRpcResFrame::End(id, Err(error()))
+            }
+            ZRpcResFrame::Batch(id, results) => {
+                ZRpcResFrame::Batch(id, results.iter().map(|_| Err(error())).collect())
+            }
+            frame => frame,
+        }
+    }
+
+    /// The context of a call received just now. Its deadline is counted from
+    /// here, as the client's timeout started when it sent the call.
+    fn context(req: &ZRpcReq, closed: &watch::Sender<bool>, peer: &Peer) -> ZRpcContext {
+        let deadline = req
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout));
+
+        ZRpcContext::for_call(deadline, closed.subscribe(), peer.clone())
+    }
+
+    /// Answers a call, along with the response headers its procedure set.
+    fn reply(id: u32, res: Result<ZRpcDt, ProcedureError>, ctx: &ZRpcContext) -> ZRpcResFrame {
+        ZRpcResFrame::Result(id, res, ctx.response_headers())
+    }
+
+    async fn call_proc(
+        req: ZRpcReq,
+        ctx: ZRpcContext,
+        registry: &Registry,
+    ) -> Result<ZRpcDt, ProcedureError> {
+        Self::dispatch(req, ctx, registry, async |req, ctx| {
+            let proc = Self::find_proc(&req.proc, registry).await;
+
+            match proc {
+                Some(proc) => match Self::acquire(&req.proc, &registry.limits).await {
+                    Ok(_permit) => proc(ctx, &req.params).await,
+                    Err(e) => Err(e),
+                },
+                None => Err(ProcedureError::MethodNotFound(req.proc.clone())),
+            }
+        })
+        .await
+    }
+
+    async fn call_upload(
+        req: ZRpcReq,
+        ctx: ZRpcContext,
+        upload: ZRpcUpload,
+        registry: &Registry,
+    ) -> Result<ZRpcDt, ProcedureError> {
+        Self::dispatch(req, ctx, registry, async move |req, ctx| {
+            let proc = registry.upload_procs.lock().await.get(&req.proc).cloned();
+
+            match proc {
+                Some(proc) => match Self::acquire(&req.proc, &registry.limits).await {
+                    Ok(_permit) => {
+                        upload.start();
+                        proc(ctx, &req.params, upload).await
+                    }
+                    Err(e) => Err(e),
+                },
+                None => Err(ProcedureError::MethodNotFound(req.proc.clone())),
+            }
+        })
+        .await
+    }
+
+    /// Runs a call through the middleware, invoking its procedure unless
+    /// middleware rejects it.
+    async fn dispatch(
+        mut req: ZRpcReq,
+        mut ctx: ZRpcContext,
+        registry: &Registry,
+        invoke: impl AsyncFnOnce(&ZRpcReq, &ZRpcContext) -> Result<ZRpcDt, ProcedureError>,
+    ) -> Result<ZRpcDt, ProcedureError> {
+        let span = registry.metrics.call(&req.proc);
+
+        let (entered, res) = registry.middleware.lock().await.enter(&mut req, &mut ctx);
+
+        let mut res = match res {
+            Ok(_) => {
+                ctx.set_headers(req.headers.clone());
+                Self::unwind(&req.proc, invoke(&req, &ctx)).await
+            }
+            Err(e) => Err(e.into()),
+        };
+
+        registry
+            .middleware
+            .lock()
+            .await
+            .leave(entered, &req, &ctx, &mut res);
+
+        span.end(CallOutcome::of(&res));
+        res
+    }
+
+    /// Turns a panic of the procedure into `ProcedureError::Internal`, so
+    /// that it only fails its own call.
+    async fn unwind<T>(
+        proc: &str,
+        fut: impl Future<Output = Result<T, ProcedureError>>,
+    ) -> Result<T, ProcedureError> {
+        AssertUnwindSafe(fut)
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|panic| Err(Self::panicked(proc, panic)))
+    }
+
+    /// Ends the stream with `ProcedureError::Internal` if producing a chunk
+    /// panics.
+    fn unwind_stream(proc: &str, stream: ProcStream) -> ProcStream {
+        let proc = proc.to_string();
+
+        AssertUnwindSafe(stream)
+            .catch_unwind()
+            .map(move |item| item.unwrap_or_else(|panic| Err(Self::panicked(&proc, panic))))
+            .boxed()
+    }
+
+    fn panicked(proc: &str, panic: Box<dyn Any + Send>) -> ProcedureError {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown payload");
+        log!("[ZRpcServer] '{}' panicked: {}", proc, message);
+
+        // The panic message stays in the log, as it may reveal internals.
+        ProcedureError::Internal("procedure panicked".to_string())
+    }
+
+    /// Takes a slot of the procedure's concurrency limit, if it has one.
+    async fn acquire(
+        name: &str,
+        limits: &Limits,
+    ) -> Result<Option<OwnedSemaphorePermit>, ProcedureError> {
+        let limit = limits.lock().await.get(name).cloned();
+
+        match limit {
+            Some(limit) => {
+                let permit = limit.acquire().await;
+
+                if permit.is_err() {
+                    log!("[ZRpcServer] '{}' is overloaded, rejecting call", name);
+                }
+
+                permit.map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn open_stream(
+        req: &ZRpcReq,
+        ctx: &ZRpcContext,
+        registry: &Registry,
+    ) -> Result<ProcStream, ProcedureError> {
+        let stream_proc = registry.stream_procs.lock().await.get(&req.proc).cloned();
+
+        if let Some(proc) = stream_proc {
+            return proc(ctx, &req.params);
+        }
+
+        // A plain procedure streams as a single chunk.
+        let proc = Self::find_proc(&req.proc, registry).await;
+
+        match proc {
+            Some(proc) => Ok(futures::stream::once(proc(ctx, &req.params)).boxed()),
+            None => Err(ProcedureError::MethodNotFound(req.proc.clone())),
+        }
+    }
+
+    /// The procedure registered as `name`, or else the fallback.
+    async fn find_proc(name: &str, registry: &Registry) -> Option<Proc> {
+        if let Some(proc) = registry.procs.lock().await.get(name) {
+            return Some(proc.clone());
+        }
+
+        let fallback = registry.fallback.lock().await.clone()?;
+        let name = name.to_string();
+
+        Some(Arc::new(move |ctx, params| fallback(ctx, &name, params)))
+    }
+
+    /// Polls the stream only while the client has granted credit. The stream
+    /// is cancelled once its credit channel is closed, either by the client's
+    /// `Cancel` or by the connection going away. Returns how the stream
+    /// ended, `Ok(ZRpcDt::Null)` unless it failed, and whether it was
+    /// cancelled.
+    async fn serve_stream(
+        id: u32,
+        mut stream: ProcStream,
+        mut credit: u32,
+        mut credits: mpsc::UnboundedReceiver<u32>,
+        frames: &Responses,
+    ) -> (Result<ZRpcDt, ProcedureError>, bool) {
+        let mut cancelled = false;
+
+        let res = loop {
+            tokio::select! {
+                n = credits.recv() => match n {
+                    Some(n) => credit = credit.saturating_add(n),
+                    None => {
+                        cancelled = true;
+                        break Ok(());
+                    }
+                },
+                item = stream.next(), if credit > 0 => match item {
+                    Some(Ok(dt)) => {
+                        if frames.send(ZRpcResFrame::Chunk(id, dt)).await.is_err() {
+                            cancelled = true;
+                            break Ok(());
+                        }
+                        credit -= 1;
+                    }
+                    Some(Err(e)) => break Err(e),
+                    None => break Ok(()),
+                },
+            }
+        };
+
+        drop(stream);
+
+        log!(
+            "[ZRpcServer:{:?}] Stream ended: {:?}",
+            std::thread::current().id(),
+            res
+        );
+
+        (res.map(|_| ZRpcDt::Null), cancelled)
+    }
 }
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc};
+
+use crate::types::frame::ZRpcResFrame;
+
+/// How many responses of a connection may wait for its writer.
+pub(crate) const RESPONSE_QUEUE: usize = 64;
+
+/// A response waiting for the writer, holding its place in the queue until
+/// it has been written.
+pub(crate) struct Outgoing {
+    pub(crate) frame: ZRpcResFrame,
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+/// Queues the responses of a connection for its writer. Answers and stream
+/// chunks wait for a free slot, so a client that reads slowly holds back
+/// its own calls instead of growing the queue.
+#[derive(Clone)]
+pub(crate) struct Responses {
+    frames: mpsc::UnboundedSender<Outgoing>,
+    slots: Arc<Semaphore>,
+}
+
+impl Responses {
+    pub(crate) fn new() -> (Self, mpsc::UnboundedReceiver<Outgoing>) {
+        let (frames, queue) = mpsc::unbounded_channel();
+        let slots = Arc::new(Semaphore::new(RESPONSE_QUEUE));
+
+        (Self { frames, slots }, queue)
+    }
+
+    /// Waits until the queue has room, e.g. before reading the next request.
+    pub(crate) async fn ready(&self) {
+        let _ = self.slots.acquire().await;
+    }
+
+    /// Queues a response, waiting while the queue is full. Fails once the
+    /// writer has stopped.
+    pub(crate) async fn send(&self, frame: ZRpcResFrame) -> Result<(), ()> {
+        let slot = self.slots.clone().acquire_owned().await.map_err(|_| ())?;
+
+        self.frames
+            .send(Outgoing {
+                frame,
+                _slot: Some(slot),
+            })
+            .map_err(|_| ())
+    }
+
+    /// Queues a frame about calls already running, such as upload credit,
+    /// right away. These are small, and holding them back could stall the
+    /// calls whose answers fill the queue.
+    pub(crate) fn send_control(&self, frame: ZRpcResFrame) {
+        let _ = self.frames.send(Outgoing { frame, _slot: None });
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use futures::Stream;
+
+use crate::types::{
+    ctx::ZRpcContext,
+    dt::ZRpcDt,
+    proc_error::ProcedureError,
+    typed::{FromZRpcParams, IntoProcResult, TypedProc},
+};
+
+use super::{Proc, StreamProc, UploadProc, upload::ZRpcUpload};
+
+#[cfg(feature = "derive")]
+pub use libzrpc_derive::zrpc_service;
+
+/// A group of related procedures, mounted onto a server under a prefix with
+/// `ZRpcServer::add_service`. A procedure `add` of a service mounted as
+/// `users` is called as `users.add`.
+#[derive(Default)]
+pub struct ZRpcService {
+    pub(super) procs: HashMap<String, Proc>,
+    pub(super) stream_procs: HashMap<String, StreamProc>,
+    pub(super) upload_procs: HashMap<String, UploadProc>,
+}
+
+impl ZRpcService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_proc<F>(&mut self, name: &str, proc: F) -> &mut Self
+    where
+        F: Fn(&Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + 'static + Send + Sync,
+    {
+        self.add_proc_with_ctx(name, move |_, params| proc(params))
+    }
+
+    pub fn add_proc_with_ctx<F>(&mut self, name: &str, proc: F) -> &mut Self
+    where
+        F: Fn(&ZRpcContext, &Vec<ZRpcDt>) -> Result<ZRpcDt, ProcedureError> + 'static + Send + Sync,
+    {
+        self.procs.insert(name.to_string(), super::sync_proc(proc));
+        self
+    }
+
+    pub fn add_async_proc<F, Fut>(&mut self, name: &str, proc: F) -> &mut Self
+    where
+        F: Fn(ZRpcContext, Vec<ZRpcDt>) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        self.procs.insert(name.to_string(), super::async_proc(proc));
+        self
+    }
+
+    pub fn add_typed_proc<Args, R>(
+        &mut self,
+        name: &str,
+        proc: impl TypedProc<Args, R>,
+    ) -> &mut Self
+    where
+        Args: FromZRpcParams,
+        R: IntoProcResult,
+    {
+        self.procs
+            .insert(name.to_string(), super::typed_proc(name, proc));
+        self
+    }
+
+    pub fn add_stream_proc<F, S>(&mut self, name: &str, proc: F) -> &mut Self
+    where
+        F: Fn(&Vec<ZRpcDt>) -> Result<S, ProcedureError> + 'static + Send + Sync,
+        S: Stream<Item = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        self.stream_procs
+            .insert(name.to_string(), super::stream_proc(proc));
+        self
+    }
+
+    pub fn add_upload_proc<F, Fut>(&mut self, name: &str, proc: F) -> &mut Self
+    where
+        F: Fn(ZRpcContext, Vec<ZRpcDt>, ZRpcUpload) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<ZRpcDt, ProcedureError>> + Send + 'static,
+    {
+        self.upload_procs
+            .insert(name.to_string(), super::upload_proc(proc));
+        self
+    }
+}
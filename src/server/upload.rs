@@ -0,0 +1,123 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    sync::mpsc,
+};
+
+use super::queue::Responses;
+use crate::types::frame::ZRpcResFrame;
+
+/// How many chunks a client may send ahead of the procedure reading them.
+pub(crate) const UPLOAD_WINDOW: u32 = 16;
+
+pub(crate) enum UploadItem {
+    Data(Vec<u8>),
+    End,
+}
+
+/// The data sent along with a call to `ZRpcClient::call_upload`, read by
+/// the upload procedure as it arrives. Reading fails with
+/// `ErrorKind::ConnectionAborted` if the client cancels the upload or goes
+/// away before finishing it.
+pub struct ZRpcUpload {
+    id: u32,
+    items: mpsc::Receiver<UploadItem>,
+    frames: Responses,
+    /// What is left of the chunk being read.
+    buf: Vec<u8>,
+    pos: usize,
+    read: u32,
+    ended: bool,
+}
+
+impl ZRpcUpload {
+    pub(crate) fn new(id: u32, items: mpsc::Receiver<UploadItem>, frames: Responses) -> Self {
+        Self {
+            id,
+            items,
+            frames,
+            buf: Vec::new(),
+            pos: 0,
+            read: 0,
+            ended: false,
+        }
+    }
+
+    /// Lets the client start sending, once the call has been accepted.
+    pub(crate) fn start(&self) {
+        self.frames
+            .send_control(ZRpcResFrame::Credit(self.id, UPLOAD_WINDOW));
+    }
+
+    /// The next chunk as the client sent it, or `None` once all of them have
+    /// been read.
+    pub async fn chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.pos < self.buf.len() {
+            let rest = self.buf.split_off(self.pos);
+            self.buf.clear();
+            self.pos = 0;
+            return Ok(Some(rest));
+        }
+
+        std::future::poll_fn(|cx| self.poll_chunk(cx)).await
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Vec<u8>>>> {
+        if self.ended {
+            return Poll::Ready(Ok(None));
+        }
+
+        match ready!(self.items.poll_recv(cx)) {
+            Some(UploadItem::Data(bytes)) => {
+                self.read += 1;
+
+                if self.read == UPLOAD_WINDOW / 2 {
+                    self.read = 0;
+                    self.frames
+                        .send_control(ZRpcResFrame::Credit(self.id, UPLOAD_WINDOW / 2));
+                }
+
+                Poll::Ready(Ok(Some(bytes)))
+            }
+            Some(UploadItem::End) => {
+                self.ended = true;
+                Poll::Ready(Ok(None))
+            }
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "upload aborted",
+            ))),
+        }
+    }
+}
+
+impl AsyncRead for ZRpcUpload {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        while this.pos == this.buf.len() {
+            match ready!(this.poll_chunk(cx))? {
+                Some(bytes) => {
+                    this.buf = bytes;
+                    this.pos = 0;
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let n = buf.remaining().min(this.buf.len() - this.pos);
+        buf.put_slice(&this.buf[this.pos..this.pos + n]);
+        this.pos += n;
+
+        Poll::Ready(Ok(()))
+    }
+}
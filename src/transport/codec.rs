@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    error::ZRpcError,
+    types::frame::{ZRpcReqFrame, ZRpcResFrame},
+};
+
+/// Encodes and decodes frames. `ZRpcCodec` provides the built-in formats;
+/// implement this to speak another one. Since the frames implement
+/// `Serialize` and `Deserialize`, a codec is usually a thin wrapper around
+/// a serde format.
+pub trait Codec: Send + Sync {
+    /// Identifies the codec on the wire. Every frame starts with it, and
+    /// peers whose tags differ fail with `ZRpcError::CodecMismatch`. Tags up
+    /// to 15 are reserved for the built-in codecs, and `0xFF` for handshakes.
+    fn tag(&self) -> u8;
+
+    /// Appends the encoded `frame` to `buf`.
+    fn encode_request(&self, frame: &ZRpcReqFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError>;
+
+    fn decode_request(&self, bytes: &[u8]) -> Result<ZRpcReqFrame, ZRpcError>;
+
+    /// Appends the encoded `frame` to `buf`.
+    fn encode_response(&self, frame: &ZRpcResFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError>;
+
+    fn decode_response(&self, bytes: &[u8]) -> Result<ZRpcResFrame, ZRpcError>;
+}
+
+/// The frames a `Codec` encodes, so transports can be generic over the
+/// direction they carry frames in.
+pub trait Frame: Sized {
+    fn encode(&self, codec: &dyn Codec, buf: &mut Vec<u8>) -> Result<(), ZRpcError>;
+
+    fn decode(codec: &dyn Codec, bytes: &[u8]) -> Result<Self, ZRpcError>;
+}
+
+impl Frame for ZRpcReqFrame {
+    fn encode(&self, codec: &dyn Codec, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        codec.encode_request(self, buf)
+    }
+
+    fn decode(codec: &dyn Codec, bytes: &[u8]) -> Result<Self, ZRpcError> {
+        codec.decode_request(bytes)
+    }
+}
+
+impl Frame for ZRpcResFrame {
+    fn encode(&self, codec: &dyn Codec, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        codec.encode_response(self, buf)
+    }
+
+    fn decode(codec: &dyn Codec, bytes: &[u8]) -> Result<Self, ZRpcError> {
+        codec.decode_response(bytes)
+    }
+}
+
+/// Encodes a frame, starting with the codec's tag.
+pub(crate) fn encode_frame<T: Frame>(codec: &dyn Codec, frame: &T) -> Result<Vec<u8>, ZRpcError> {
+    let mut bytes = vec![codec.tag()];
+    frame.encode(codec, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Names a codec in errors, by its tag.
+pub(crate) fn describe(tag: u8) -> String {
+    match ZRpcCodec::from_tag(tag) {
+        Ok(codec) => format!("{:?}", codec),
+        Err(_) => format!("the codec tagged {}", tag),
+    }
+}
+
+/// The built-in formats frames are encoded in. Both peers must use the same one, which is
+/// checked on every frame. Every variant exists in every build, but
+/// `MessagePack` is only usable with the `msgpack` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ZRpcCodec {
+    #[default]
+    Bincode,
+    /// Structs are encoded as maps keyed by field name, so that peers in
+    /// other languages can decode them.
+    MessagePack,
+    /// Readable JSON, e.g. for debugging. Values keep their `ZRpcDt` variant
+    /// as a tag, bytes are base64 strings and 64-bit integers are strings,
+    /// so everything round-trips exactly.
+    Json,
+}
+
+impl ZRpcCodec {
+    pub fn is_available(self) -> bool {
+        match self {
+            ZRpcCodec::Bincode => true,
+            ZRpcCodec::MessagePack => cfg!(feature = "msgpack"),
+            ZRpcCodec::Json => true,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Result<Self, ZRpcError> {
+        match tag {
+            0 => Ok(ZRpcCodec::Bincode),
+            1 => Ok(ZRpcCodec::MessagePack),
+            2 => Ok(ZRpcCodec::Json),
+            _ => Err(ZRpcError::Serialization(format!(
+                "Unknown codec tag {}",
+                tag
+            ))),
+        }
+    }
+
+    /// Appends the encoded `value` to `buf`.
+    fn encode<T: Serialize>(self, value: &T, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        match self {
+            ZRpcCodec::Bincode => bincode::serialize_into(buf, value)
+                .map_err(|e| ZRpcError::Serialization(e.to_string())),
+            #[cfg(feature = "msgpack")]
+            ZRpcCodec::MessagePack => rmp_serde::encode::write_named(buf, value)
+                .map_err(|e| ZRpcError::Serialization(e.to_string())),
+            ZRpcCodec::Json => serde_json::to_writer(buf, value)
+                .map_err(|e| ZRpcError::Serialization(e.to_string())),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unavailable()),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, ZRpcError> {
+        match self {
+            ZRpcCodec::Bincode => {
+                bincode::deserialize(bytes).map_err(|e| ZRpcError::Serialization(e.to_string()))
+            }
+            #[cfg(feature = "msgpack")]
+            ZRpcCodec::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| ZRpcError::Serialization(e.to_string()))
+            }
+            ZRpcCodec::Json => {
+                serde_json::from_slice(bytes).map_err(|e| ZRpcError::Serialization(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unavailable()),
+        }
+    }
+
+    fn unavailable(self) -> ZRpcError {
+        ZRpcError::Serialization(format!("The {:?} codec is not enabled in this build", self))
+    }
+}
+
+impl Codec for ZRpcCodec {
+    fn tag(&self) -> u8 {
+        match self {
+            ZRpcCodec::Bincode => 0,
+            ZRpcCodec::MessagePack => 1,
+            ZRpcCodec::Json => 2,
+        }
+    }
+
+    fn encode_request(&self, frame: &ZRpcReqFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        self.encode(frame, buf)
+    }
+
+    fn decode_request(&self, bytes: &[u8]) -> Result<ZRpcReqFrame, ZRpcError> {
+        self.decode(bytes)
+    }
+
+    fn encode_response(&self, frame: &ZRpcResFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        self.encode(frame, buf)
+    }
+
+    fn decode_response(&self, bytes: &[u8]) -> Result<ZRpcResFrame, ZRpcError> {
+        self.decode(bytes)
+    }
+}
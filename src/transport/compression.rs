@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::ZRpcError;
+
+/// Algorithms a connection can compress frames with. Every variant exists in
+/// every build so the wire format doesn't depend on enabled features, but an
+/// algorithm is only used when its feature (`lz4`, `zstd`) is enabled on both
+/// peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ZRpcCompression {
+    Lz4,
+    Zstd,
+}
+
+impl ZRpcCompression {
+    pub fn is_available(self) -> bool {
+        match self {
+            ZRpcCompression::Lz4 => cfg!(feature = "lz4"),
+            ZRpcCompression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    /// The algorithms this build can decompress.
+    pub fn available() -> Vec<Self> {
+        [ZRpcCompression::Lz4, ZRpcCompression::Zstd]
+            .into_iter()
+            .filter(|c| c.is_available())
+            .collect()
+    }
+
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            ZRpcCompression::Lz4 => 0,
+            ZRpcCompression::Zstd => 1,
+        }
+    }
+
+    pub(crate) fn from_tag(tag: u8) -> Result<Self, ZRpcError> {
+        match tag {
+            0 => Ok(ZRpcCompression::Lz4),
+            1 => Ok(ZRpcCompression::Zstd),
+            _ => Err(ZRpcError::Serialization(format!(
+                "Unknown compression tag {}",
+                tag
+            ))),
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn compress(self, bytes: &[u8]) -> Result<Vec<u8>, ZRpcError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            ZRpcCompression::Lz4 => Ok(lz4_flex::compress_prepend_size(bytes)),
+            #[cfg(feature = "zstd")]
+            ZRpcCompression::Zstd => zstd::bulk::compress(bytes, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .map_err(|e| ZRpcError::Serialization(e.to_string())),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unavailable()),
+        }
+    }
+
+    /// Fails with `ZRpcError::FrameTooLarge` rather than decompressing more
+    /// than `max` bytes.
+    #[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn decompress(self, bytes: &[u8], max: usize) -> Result<Vec<u8>, ZRpcError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            ZRpcCompression::Lz4 => {
+                let (size, _) = lz4_flex::block::uncompressed_size(bytes)
+                    .map_err(|e| ZRpcError::Serialization(e.to_string()))?;
+
+                if size > max {
+                    return Err(ZRpcError::FrameTooLarge(max));
+                }
+
+                lz4_flex::decompress_size_prepended(bytes)
+                    .map_err(|e| ZRpcError::Serialization(e.to_string()))
+            }
+            #[cfg(feature = "zstd")]
+            ZRpcCompression::Zstd => {
+                use std::io::Read;
+
+                let mut buf = Vec::new();
+                zstd::stream::read::Decoder::new(bytes)
+                    .and_then(|decoder| decoder.take(max as u64 + 1).read_to_end(&mut buf))
+                    .map_err(|e| ZRpcError::Serialization(e.to_string()))?;
+
+                if buf.len() > max {
+                    return Err(ZRpcError::FrameTooLarge(max));
+                }
+
+                Ok(buf)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(self.unavailable()),
+        }
+    }
+
+    fn unavailable(self) -> ZRpcError {
+        ZRpcError::Serialization(format!(
+            "{:?} compression is not enabled in this build",
+            self
+        ))
+    }
+}
+
+/// What a peer compresses its outgoing frames with, once the other side has
+/// announced it can decompress them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Compression {
+    pub(crate) algorithm: ZRpcCompression,
+    /// Frames smaller than this are sent as they are.
+    pub(crate) threshold: usize,
+}
+
+impl Compression {
+    pub(crate) const DEFAULT_THRESHOLD: usize = 1024;
+
+    /// Keeps the compression only if the peer can decompress it.
+    pub(crate) fn negotiate(self, accepted: &[ZRpcCompression]) -> Option<Self> {
+        (self.algorithm.is_available() && accepted.contains(&self.algorithm)).then_some(self)
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::ZRpcError;
+
+use super::{codec::Codec, compression::ZRpcCompression};
+
+/// Bumped whenever peers speaking different versions would misunderstand
+/// each other.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The oldest protocol version this build still speaks.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Takes the place of the codec tag in handshake frames.
+pub(crate) const HELLO_TAG: u8 = 0xFF;
+
+/// Exchanged when a connection is opened, always encoded as JSON so that it
+/// can be read whatever codec the peers use. The client says what it
+/// supports, and the server answers with what the connection uses, or with
+/// why it can't serve the client. Unknown fields are ignored and missing
+/// ones take their default, so fields can be added without breaking older
+/// peers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Hello {
+    pub(crate) protocol: u32,
+    /// The crate version of the sender.
+    pub(crate) version: String,
+    /// Codec tags. The server answers with the one it uses.
+    pub(crate) codecs: Vec<u8>,
+    /// Tags of the compression algorithms the sender can decompress.
+    pub(crate) compression: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl Hello {
+    pub(crate) fn new(protocol: u32, codec: &dyn Codec) -> Self {
+        Self {
+            protocol,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            codecs: vec![codec.tag()],
+            compression: ZRpcCompression::available()
+                .into_iter()
+                .map(ZRpcCompression::tag)
+                .collect(),
+            error: None,
+        }
+    }
+
+    /// Turns down a client, still telling it the server's codec.
+    pub(crate) fn refuse(codec: &dyn Codec, error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::new(PROTOCOL_VERSION, codec)
+        }
+    }
+
+    /// The listed compression algorithms this build knows of.
+    pub(crate) fn compression(&self) -> Vec<ZRpcCompression> {
+        self.compression
+            .iter()
+            .filter_map(|tag| ZRpcCompression::from_tag(*tag).ok())
+            .collect()
+    }
+
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, ZRpcError> {
+        let mut bytes = vec![HELLO_TAG];
+        serde_json::to_writer(&mut bytes, self)
+            .map_err(|e| ZRpcError::Serialization(e.to_string()))?;
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, ZRpcError> {
+        serde_json::from_slice(bytes).map_err(|e| ZRpcError::Serialization(e.to_string()))
+    }
+
+    /// Whether a peer speaking `protocol` can be talked to.
+    pub(crate) fn is_supported(protocol: u32) -> bool {
+        (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol)
+    }
+}
@@ -1 +1,16 @@
+use tokio::io::{AsyncRead, AsyncWrite};
+
+pub mod codec;
+pub mod compression;
+pub mod handshake;
 pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(unix)]
+pub(crate) mod unix;
+
+/// A byte stream `TcpTransport` can frame messages over, such as a TCP or
+/// Unix domain socket, a TLS session or an in-memory duplex pipe.
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
@@ -1,31 +1,111 @@
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::{error::ZRpcError, log};
 
+use super::{
+    AsyncStream,
+    codec::{self, Codec, Frame, ZRpcCodec},
+    compression::{Compression, ZRpcCompression},
+    handshake::{HELLO_TAG, Hello},
+};
+
+/// Set in the length prefix of frames whose payload is compressed. The
+/// payload then starts with the algorithm's tag.
+const COMPRESSED: u32 = 1 << 31;
+
 pub struct TcpTransport {
-    stream: TcpStream,
+    reader: TcpReader,
+    writer: TcpWriter,
 }
 
 impl TcpTransport {
-    pub fn new(stream: TcpStream) -> Self {
-        Self { stream }
+    pub(crate) const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn new<S: AsyncStream + 'static>(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+
+        Self {
+            reader: TcpReader {
+                stream: Box::new(reader),
+                max_frame_size: Self::DEFAULT_MAX_FRAME_SIZE,
+                codec: Arc::new(ZRpcCodec::default()),
+            },
+            writer: TcpWriter {
+                stream: Box::new(writer),
+                compression: None,
+                codec: Arc::new(ZRpcCodec::default()),
+            },
+        }
+    }
+
+    pub(crate) fn set_compression(&mut self, compression: Option<Compression>) {
+        self.writer.compression = compression;
+    }
+
+    pub(crate) fn set_codec(&mut self, codec: Arc<dyn Codec>) {
+        self.reader.codec = codec.clone();
+        self.writer.codec = codec;
+    }
+
+    pub(crate) fn codec(&self) -> Arc<dyn Codec> {
+        self.writer.codec.clone()
+    }
+
+    pub(crate) async fn send_hello(&mut self, hello: &Hello) -> Result<(), ZRpcError> {
+        self.writer.send(&hello.encode()?).await
+    }
+
+    /// Receives the peer's handshake, failing with
+    /// `ZRpcError::IncompatibleProtocol` if it sent anything else.
+    pub(crate) async fn receive_hello(&mut self) -> Result<Hello, ZRpcError> {
+        let bytes = self.reader.receive().await?;
+
+        match bytes.split_first() {
+            Some((&HELLO_TAG, hello)) => Hello::decode(hello),
+            _ => Err(ZRpcError::IncompatibleProtocol(
+                "the peer did not open with a handshake".to_string(),
+            )),
+        }
+    }
+
+    /// Frames above `max` bytes, before or after decompression, are
+    /// rejected without reading them into memory.
+    pub(crate) fn set_max_frame_size(&mut self, max: usize) {
+        self.reader.max_frame_size = max;
+    }
+
+    /// Splits the transport so frames can be received and sent from
+    /// different tasks.
+    pub fn into_split(self) -> (TcpReader, TcpWriter) {
+        (self.reader, self.writer)
     }
 
     pub async fn send(&mut self, bytes: &[u8]) -> Result<(), ZRpcError> {
-        let len = (bytes.len() as u32).to_be_bytes();
-        self.stream.write_all(&len).await.map_err(ZRpcError::Io)?;
-        self.stream.write_all(bytes).await.map_err(ZRpcError::Io)?;
+        self.writer.send(bytes).await
+    }
 
-        log!(
-            "[TcpTransport:{:?}] {} bytes were sent",
-            std::thread::current().id(),
-            len.len() + bytes.len()
-        );
+    pub async fn receive(&mut self) -> Result<Vec<u8>, ZRpcError> {
+        self.reader.receive().await
+    }
 
-        Ok(())
+    pub async fn send_frame<T: Frame>(&mut self, frame: &T) -> Result<(), ZRpcError> {
+        self.writer.send_frame(frame).await
+    }
+
+    pub async fn receive_frame<T: Frame>(&mut self) -> Result<T, ZRpcError> {
+        self.reader.receive_frame().await
     }
+}
+
+pub struct TcpReader {
+    stream: Box<dyn AsyncRead + Unpin + Send>,
+    max_frame_size: usize,
+    codec: Arc<dyn Codec>,
+}
 
+impl TcpReader {
     pub async fn receive(&mut self) -> Result<Vec<u8>, ZRpcError> {
         let mut len = [0u8; 4];
         self.stream
@@ -33,7 +113,19 @@ impl TcpTransport {
             .await
             .map_err(ZRpcError::Io)?;
 
-        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        let len = u32::from_be_bytes(len);
+        let size = (len & !COMPRESSED) as usize;
+
+        if size > self.max_frame_size {
+            log!(
+                "[TcpTransport:{:?}] Rejected a frame of {} bytes",
+                std::thread::current().id(),
+                size
+            );
+            return Err(ZRpcError::FrameTooLarge(self.max_frame_size));
+        }
+
+        let mut buf = vec![0u8; size];
         self.stream
             .read_exact(&mut buf)
             .await
@@ -42,9 +134,104 @@ impl TcpTransport {
         log!(
             "[TcpTransport:{:?}] {} bytes were received",
             std::thread::current().id(),
-            len.len() + buf.len()
+            4 + buf.len()
         );
 
-        Ok(buf)
+        if len & COMPRESSED == 0 {
+            return Ok(buf);
+        }
+
+        let (tag, bytes) = buf
+            .split_first()
+            .ok_or_else(|| ZRpcError::Serialization("Empty compressed frame".to_string()))?;
+
+        ZRpcCompression::from_tag(*tag)?.decompress(bytes, self.max_frame_size)
+    }
+
+    pub async fn receive_frame<T: Frame>(&mut self) -> Result<T, ZRpcError> {
+        let bytes = self.receive().await?;
+        self.decode_frame(&bytes)
+    }
+
+    fn decode_frame<T: Frame>(&self, bytes: &[u8]) -> Result<T, ZRpcError> {
+        let (tag, bytes) = bytes
+            .split_first()
+            .ok_or_else(|| ZRpcError::Serialization("Empty frame".to_string()))?;
+
+        if *tag == HELLO_TAG {
+            return Err(ZRpcError::Serialization("Unexpected handshake".to_string()));
+        }
+
+        if *tag != self.codec.tag() {
+            return Err(ZRpcError::CodecMismatch {
+                local: self.codec.tag(),
+                peer: *tag,
+            });
+        }
+
+        T::decode(&*self.codec, bytes)
+    }
+}
+
+pub struct TcpWriter {
+    stream: Box<dyn AsyncWrite + Unpin + Send>,
+    compression: Option<Compression>,
+    codec: Arc<dyn Codec>,
+}
+
+impl TcpWriter {
+    pub async fn send(&mut self, bytes: &[u8]) -> Result<(), ZRpcError> {
+        let compressed = match self.compression {
+            Some(c) if bytes.len() >= c.threshold => {
+                let mut buf = vec![c.algorithm.tag()];
+                buf.extend(c.algorithm.compress(bytes)?);
+
+                // Incompressible payloads are cheaper to send as they are.
+                (buf.len() < bytes.len()).then_some(buf)
+            }
+            _ => None,
+        };
+
+        let (flag, bytes) = match &compressed {
+            Some(buf) => (COMPRESSED, buf.as_slice()),
+            None => (0, bytes),
+        };
+
+        let len = u32::try_from(bytes.len())
+            .ok()
+            .filter(|len| len & COMPRESSED == 0)
+            .ok_or_else(|| {
+                ZRpcError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "frame exceeds 2 GiB",
+                ))
+            })?;
+
+        // One write per frame, or Nagle's algorithm holds the payload back
+        // until the peer acknowledges the length.
+        let mut frame = Vec::with_capacity(4 + bytes.len());
+        frame.extend((len | flag).to_be_bytes());
+        frame.extend(bytes);
+        self.stream.write_all(&frame).await.map_err(ZRpcError::Io)?;
+
+        log!(
+            "[TcpTransport:{:?}] {} bytes were sent",
+            std::thread::current().id(),
+            frame.len()
+        );
+
+        Ok(())
+    }
+
+    /// Fails with `ZRpcError::Serialization` before anything is written if
+    /// the frame can't be encoded.
+    pub async fn send_frame<T: Frame>(&mut self, frame: &T) -> Result<(), ZRpcError> {
+        let bytes = codec::encode_frame(&*self.codec, frame)?;
+
+        self.send(&bytes).await
+    }
+
+    pub async fn shutdown(&mut self) -> Result<(), ZRpcError> {
+        self.stream.shutdown().await.map_err(ZRpcError::Io)
     }
 }
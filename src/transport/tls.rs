@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+pub use tokio_rustls::rustls;
+
+use rustls::{
+    ClientConfig, RootCertStore, ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+};
+
+use crate::{error::ZRpcError, types::ctx::ZRpcPeerIdentity};
+
+/// Builds a client config trusting `roots`. Passing an `identity` presents a
+/// client certificate to servers that require mutual TLS.
+pub fn client_config(
+    roots: RootCertStore,
+    identity: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> Result<Arc<ClientConfig>, ZRpcError> {
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+
+    let config = match identity {
+        Some((chain, key)) => builder
+            .with_client_auth_cert(chain, key)
+            .map_err(|e| ZRpcError::Tls(e.to_string()))?,
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(Arc::new(config))
+}
+
+/// Builds a server config presenting `chain`. With `client_roots`, clients
+/// must present a certificate signed by one of those roots.
+pub fn server_config(
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_roots: Option<RootCertStore>,
+) -> Result<Arc<ServerConfig>, ZRpcError> {
+    let builder = match client_roots {
+        Some(roots) => {
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .map_err(|e| ZRpcError::Tls(e.to_string()))?;
+
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let config = builder
+        .with_single_cert(chain, key)
+        .map_err(|e| ZRpcError::Tls(e.to_string()))?;
+
+    Ok(Arc::new(config))
+}
+
+/// The identity of a client that presented a certificate, which rustls has
+/// verified by the time the handshake completes.
+pub(crate) fn peer_identity(conn: &rustls::ServerConnection) -> Option<ZRpcPeerIdentity> {
+    let chain = conn.peer_certificates()?;
+    let (_, cert) = x509_parser::parse_x509_certificate(chain.first()?).ok()?;
+
+    Some(ZRpcPeerIdentity::new(
+        cert.subject().to_string(),
+        chain.iter().map(|cert| cert.to_vec()).collect(),
+    ))
+}
+
+#[derive(Clone)]
+pub(crate) struct TlsConnect {
+    pub(crate) connector: tokio_rustls::TlsConnector,
+    pub(crate) server_name: rustls::pki_types::ServerName<'static>,
+}
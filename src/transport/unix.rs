@@ -0,0 +1,88 @@
+use std::{
+    fs::DirBuilder,
+    io::{Error, ErrorKind},
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use tokio::net::{UnixListener, UnixStream};
+
+/// A listening Unix domain socket. The socket file is removed again when it
+/// is dropped.
+pub(crate) struct UnixSocket {
+    pub(crate) listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixSocket {
+    /// Binds `path`, replacing a socket file left behind by a server that is
+    /// no longer running. Fails if another server is still listening on it,
+    /// or if `path` is not a socket.
+    pub(crate) async fn bind(path: &Path, mode: Option<u32>) -> std::io::Result<Self> {
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+
+            match UnixStream::connect(path).await {
+                Ok(_) => {
+                    return Err(Error::new(
+                        ErrorKind::AddrInUse,
+                        format!("{} is in use by another server", path.display()),
+                    ));
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                    std::fs::remove_file(path)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let listener = match mode {
+            Some(mode) => Self::bind_private(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Binds the socket in a directory only this process can enter, and
+    /// moves it to `path` once it has `mode`, so it's never reachable with
+    /// the looser permissions it's created with.
+    fn bind_private(path: &Path, mode: u32) -> std::io::Result<UnixListener> {
+        let file_name = path.file_name().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a file path", path.display()),
+            )
+        })?;
+        let mut dir_name = std::ffi::OsString::from(".");
+        dir_name.push(file_name);
+        dir_name.push(format!(".{}", std::process::id()));
+        let dir = path.with_file_name(dir_name);
+
+        DirBuilder::new().mode(0o700).create(&dir)?;
+        let private = dir.join(file_name);
+        let res = UnixListener::bind(&private).and_then(|listener| {
+            std::fs::set_permissions(&private, std::fs::Permissions::from_mode(mode))?;
+            std::fs::rename(&private, path)?;
+            Ok(listener)
+        });
+
+        let _ = std::fs::remove_file(&private);
+        let _ = std::fs::remove_dir(&dir);
+        res
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
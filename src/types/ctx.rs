@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+
+use super::{dt::ZRpcDt, req::ZRpcHeaders};
+
+/// The client a connection was opened by.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Peer {
+    pub(crate) addr: Option<SocketAddr>,
+    pub(crate) identity: Option<Arc<ZRpcPeerIdentity>>,
+}
+
+/// The certificate a client presented over mutual TLS, which has been
+/// verified against the server's client roots.
+#[derive(Debug, Clone)]
+pub struct ZRpcPeerIdentity {
+    subject: String,
+    certificates: Vec<Vec<u8>>,
+}
+
+impl ZRpcPeerIdentity {
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub(crate) fn new(subject: String, certificates: Vec<Vec<u8>>) -> Self {
+        Self {
+            subject,
+            certificates,
+        }
+    }
+
+    /// The subject of the client's certificate, e.g. `CN=alice, O=Example`.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The DER-encoded certificates the client presented, its own first.
+    pub fn certificates(&self) -> &[Vec<u8>] {
+        &self.certificates
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ZRpcContext {
+    values: HashMap<String, ZRpcDt>,
+    started_at: Instant,
+    deadline: Option<Instant>,
+    closed: Option<watch::Receiver<bool>>,
+    headers: ZRpcHeaders,
+    /// Shared by every copy of the context, so that async procedures can set
+    /// them too.
+    response_headers: Arc<Mutex<ZRpcHeaders>>,
+    peer: Peer,
+}
+
+impl ZRpcContext {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            started_at: Instant::now(),
+            deadline: None,
+            closed: None,
+            headers: ZRpcHeaders::new(),
+            response_headers: Arc::default(),
+            peer: Peer::default(),
+        }
+    }
+
+    /// A context for a call received on a connection, cancelled once
+    /// `closed` turns true or its sender is dropped.
+    pub(crate) fn for_call(
+        deadline: Option<Instant>,
+        closed: watch::Receiver<bool>,
+        peer: Peer,
+    ) -> Self {
+        Self {
+            deadline,
+            closed: Some(closed),
+            peer,
+            ..Self::new()
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, value: ZRpcDt) -> Option<ZRpcDt> {
+        self.values.insert(key.to_string(), value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ZRpcDt> {
+        self.values.get(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<ZRpcDt> {
+        self.values.remove(key)
+    }
+
+    /// A header the client sent with the call, as left by middleware.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get(key).map(String::as_str)
+    }
+
+    pub fn headers(&self) -> &ZRpcHeaders {
+        &self.headers
+    }
+
+    pub(crate) fn set_headers(&mut self, headers: ZRpcHeaders) {
+        self.headers = headers;
+    }
+
+    /// Sends a header back to the client along with the answer. Has no
+    /// effect on streams, batches and notifications.
+    pub fn set_response_header(&self, key: &str, value: &str) -> Option<String> {
+        self.response_headers
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_string())
+    }
+
+    pub fn response_headers(&self) -> ZRpcHeaders {
+        self.response_headers.lock().unwrap().clone()
+    }
+
+    /// The address of the client, for calls received over TCP.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer.addr
+    }
+
+    /// The verified certificate of the client, for calls received over TLS
+    /// from a client that presented one.
+    pub fn peer_identity(&self) -> Option<&ZRpcPeerIdentity> {
+        self.peer.identity.as_deref()
+    }
+
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// When the caller stops waiting for the answer, if it said so.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// The time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether nobody is waiting for the answer anymore, because the
+    /// connection closed or the deadline passed.
+    pub fn is_cancelled(&self) -> bool {
+        let closed = self
+            .closed
+            .as_ref()
+            .is_some_and(|closed| *closed.borrow() || closed.has_changed().is_err());
+
+        closed
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Resolves once the call is cancelled, see `is_cancelled`. Never
+    /// resolves for a context without a connection or a deadline.
+    pub async fn cancelled(&self) {
+        let closed = async {
+            match &self.closed {
+                Some(closed) => {
+                    let _ = closed.clone().wait_for(|closed| *closed).await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = closed => {}
+            _ = deadline => {}
+        }
+    }
+}
+
+impl Default for ZRpcContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,17 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "derive")]
+pub use libzrpc_derive::{ZRpcDeserialize, ZRpcSerialize};
+
+pub const MAX_DEPTH: usize = 64;
+
 pub trait ZRpcDtAuto {
     fn to_zdt(&self) -> ZRpcDt;
 }
@@ -82,9 +94,62 @@ impl ZRpcDtAuto for bool {
     }
 }
 
+impl ZRpcDtAuto for SystemTime {
+    fn to_zdt(&self) -> ZRpcDt {
+        (*self).into_zdt()
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl ZRpcDtAuto for uuid::Uuid {
+    fn to_zdt(&self) -> ZRpcDt {
+        ZRpcDt::Uuid(*self.as_bytes())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ZRpcDtAuto for chrono::DateTime<chrono::Utc> {
+    fn to_zdt(&self) -> ZRpcDt {
+        (*self).into_zdt()
+    }
+}
+
+impl<T: ZRpcDtAuto + Serialize> ZRpcDtAuto for Vec<T> {
+    default fn to_zdt(&self) -> ZRpcDt {
+        ZRpcDt::Array(self.iter().map(ZRpcDtAuto::to_zdt).collect())
+    }
+}
+
 impl ZRpcDtAuto for Vec<u8> {
     fn to_zdt(&self) -> ZRpcDt {
-        ZRpcDt::Serialized(self.clone())
+        ZRpcDt::Bytes(self.clone())
+    }
+}
+
+impl ZRpcDtAuto for &[u8] {
+    fn to_zdt(&self) -> ZRpcDt {
+        ZRpcDt::Bytes(self.to_vec())
+    }
+}
+
+impl<T: ZRpcDtAuto + Serialize> ZRpcDtAuto for HashMap<String, T> {
+    fn to_zdt(&self) -> ZRpcDt {
+        ZRpcDt::Map(self.iter().map(|(k, v)| (k.clone(), v.to_zdt())).collect())
+    }
+}
+
+impl<T: ZRpcDtAuto + Serialize> ZRpcDtAuto for Option<T> {
+    fn to_zdt(&self) -> ZRpcDt {
+        match self {
+            Some(v) => v.to_zdt(),
+            None => ZRpcDt::Null,
+        }
+    }
+}
+
+impl ZRpcDtAuto for ZRpcDt {
+    fn to_zdt(&self) -> ZRpcDt {
+        self.clone()
     }
 }
 
@@ -94,29 +159,386 @@ impl<T: Serialize> ZRpcDtAuto for T {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub trait IntoZRpcDt {
+    fn into_zdt(self) -> ZRpcDt;
+}
+
+pub trait FromZRpcDt: Sized {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError>;
+}
+
+#[derive(Debug)]
+pub struct ZRpcDtError(pub String);
+
+impl ZRpcDtError {
+    pub fn mismatch(expected: &str, found: &ZRpcDt) -> Self {
+        Self(format!("expected {}, found {}", expected, found.kind()))
+    }
+}
+
+impl Display for ZRpcDtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ZRpcDtError {}
+
+macro_rules! impl_int_conversions {
+    ($($t:ty => $variant:ident),*) => {
+        $(
+            impl IntoZRpcDt for $t {
+                fn into_zdt(self) -> ZRpcDt {
+                    ZRpcDt::$variant(self)
+                }
+            }
+
+            impl FromZRpcDt for $t {
+                fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+                    let v = match *dt {
+                        ZRpcDt::Int8(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::Int16(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::Int32(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::Int64(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::UInt8(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::UInt16(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::UInt32(v) => <$t>::try_from(v).ok(),
+                        ZRpcDt::UInt64(v) => <$t>::try_from(v).ok(),
+                        _ => return Err(ZRpcDtError::mismatch(stringify!($t), dt)),
+                    };
+
+                    v.ok_or_else(|| {
+                        ZRpcDtError(format!("{:?} is out of range for {}", dt, stringify!($t)))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_int_conversions!(
+    i8 => Int8, i16 => Int16, i32 => Int32, i64 => Int64,
+    u8 => UInt8, u16 => UInt16, u32 => UInt32, u64 => UInt64
+);
+
+impl IntoZRpcDt for f32 {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Float32(self)
+    }
+}
+
+impl FromZRpcDt for f32 {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match *dt {
+            ZRpcDt::Float32(v) => Ok(v),
+            _ => Err(ZRpcDtError::mismatch("f32", dt)),
+        }
+    }
+}
+
+impl IntoZRpcDt for f64 {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Float64(self)
+    }
+}
+
+impl FromZRpcDt for f64 {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match *dt {
+            ZRpcDt::Float32(v) => Ok(v as f64),
+            ZRpcDt::Float64(v) => Ok(v),
+            _ => Err(ZRpcDtError::mismatch("f64", dt)),
+        }
+    }
+}
+
+impl IntoZRpcDt for bool {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Bool(self)
+    }
+}
+
+impl FromZRpcDt for bool {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match *dt {
+            ZRpcDt::Bool(v) => Ok(v),
+            _ => Err(ZRpcDtError::mismatch("bool", dt)),
+        }
+    }
+}
+
+impl IntoZRpcDt for String {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::String(self)
+    }
+}
+
+impl IntoZRpcDt for &str {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::String(self.to_string())
+    }
+}
+
+impl FromZRpcDt for String {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::String(v) => Ok(v.clone()),
+            _ => Err(ZRpcDtError::mismatch("String", dt)),
+        }
+    }
+}
+
+/// Times outside the roughly 584 years around 1970 that fit in
+/// `ZRpcDt::Timestamp` are clamped to its range.
+impl IntoZRpcDt for SystemTime {
+    fn into_zdt(self) -> ZRpcDt {
+        let nanos = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        };
+
+        ZRpcDt::Timestamp(nanos.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+}
+
+impl FromZRpcDt for SystemTime {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        let ZRpcDt::Timestamp(nanos) = *dt else {
+            return Err(ZRpcDtError::mismatch("SystemTime", dt));
+        };
+
+        let offset = Duration::from_nanos(nanos.unsigned_abs());
+        let time = if nanos < 0 {
+            UNIX_EPOCH.checked_sub(offset)
+        } else {
+            UNIX_EPOCH.checked_add(offset)
+        };
+
+        time.ok_or_else(|| ZRpcDtError(format!("{:?} is out of range for SystemTime", dt)))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl IntoZRpcDt for uuid::Uuid {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Uuid(self.into_bytes())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromZRpcDt for uuid::Uuid {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match *dt {
+            ZRpcDt::Uuid(bytes) => Ok(uuid::Uuid::from_bytes(bytes)),
+            _ => Err(ZRpcDtError::mismatch("Uuid", dt)),
+        }
+    }
+}
+
+/// Clamped like `SystemTime`.
+#[cfg(feature = "chrono")]
+impl IntoZRpcDt for chrono::DateTime<chrono::Utc> {
+    fn into_zdt(self) -> ZRpcDt {
+        let nanos = self
+            .timestamp_nanos_opt()
+            .unwrap_or(if self.timestamp() < 0 {
+                i64::MIN
+            } else {
+                i64::MAX
+            });
+
+        ZRpcDt::Timestamp(nanos)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl FromZRpcDt for chrono::DateTime<chrono::Utc> {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match *dt {
+            ZRpcDt::Timestamp(nanos) => Ok(chrono::DateTime::from_timestamp_nanos(nanos)),
+            _ => Err(ZRpcDtError::mismatch("DateTime<Utc>", dt)),
+        }
+    }
+}
+
+impl<T: IntoZRpcDt> IntoZRpcDt for Vec<T> {
+    default fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Array(self.into_iter().map(IntoZRpcDt::into_zdt).collect())
+    }
+}
+
+/// Elements may be of different kinds in a `ZRpcDt::Array`, but every one of
+/// them has to convert to `T`.
+impl<T: FromZRpcDt> FromZRpcDt for Vec<T> {
+    default fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| T::from_zdt(v).map_err(|e| ZRpcDtError(format!("[{}]: {}", i, e))))
+                .collect(),
+            _ => Err(ZRpcDtError::mismatch("Array", dt)),
+        }
+    }
+}
+
+impl IntoZRpcDt for Vec<u8> {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Bytes(self)
+    }
+}
+
+impl FromZRpcDt for Vec<u8> {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::Bytes(v) => Ok(v.clone()),
+            ZRpcDt::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| u8::from_zdt(v).map_err(|e| ZRpcDtError(format!("[{}]: {}", i, e))))
+                .collect(),
+            _ => Err(ZRpcDtError::mismatch("Bytes", dt)),
+        }
+    }
+}
+
+impl<T: IntoZRpcDt> IntoZRpcDt for HashMap<String, T> {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Map(self.into_iter().map(|(k, v)| (k, v.into_zdt())).collect())
+    }
+}
+
+impl<T: FromZRpcDt> FromZRpcDt for HashMap<String, T> {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::Map(map) => map
+                .iter()
+                .map(|(k, v)| {
+                    T::from_zdt(v)
+                        .map(|v| (k.clone(), v))
+                        .map_err(|e| ZRpcDtError(format!("{}: {}", k, e)))
+                })
+                .collect(),
+            _ => Err(ZRpcDtError::mismatch("Map", dt)),
+        }
+    }
+}
+
+impl IntoZRpcDt for () {
+    fn into_zdt(self) -> ZRpcDt {
+        ZRpcDt::Ok
+    }
+}
+
+impl FromZRpcDt for () {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::Ok => Ok(()),
+            _ => Err(ZRpcDtError::mismatch("Ok", dt)),
+        }
+    }
+}
+
+impl<T: IntoZRpcDt> IntoZRpcDt for Option<T> {
+    fn into_zdt(self) -> ZRpcDt {
+        match self {
+            Some(v) => v.into_zdt(),
+            None => ZRpcDt::Null,
+        }
+    }
+}
+
+impl<T: FromZRpcDt> FromZRpcDt for Option<T> {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        match dt {
+            ZRpcDt::Null => Ok(None),
+            _ => T::from_zdt(dt).map(Some),
+        }
+    }
+}
+
+impl IntoZRpcDt for ZRpcDt {
+    fn into_zdt(self) -> ZRpcDt {
+        self
+    }
+}
+
+impl FromZRpcDt for ZRpcDt {
+    fn from_zdt(dt: &ZRpcDt) -> Result<Self, ZRpcDtError> {
+        Ok(dt.clone())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ZRpcDt {
     Int8(i8),
     Int16(i16),
     Int32(i32),
+    #[serde(with = "int64")]
     Int64(i64),
     UInt8(u8),
     UInt16(u16),
     UInt32(u32),
+    #[serde(with = "int64")]
     UInt64(u64),
+    #[serde(with = "float")]
     Float32(f32),
+    #[serde(with = "float")]
     Float64(f64),
     String(String),
     Bool(bool),
+    #[serde(with = "bytes")]
     Serialized(Vec<u8>),
     Ok,
+    #[serde(with = "bytes")]
+    Bytes(Vec<u8>),
+    #[serde(with = "depth")]
+    Map(HashMap<String, ZRpcDt>),
+    /// The absence of a value, produced for `None`.
+    Null,
+    /// A list, produced for `Vec<T>` other than `Vec<u8>`.
+    #[serde(with = "depth")]
+    Array(Vec<ZRpcDt>),
+    /// A UUID, converting to and from `uuid::Uuid` with the `uuid` feature.
+    #[serde(with = "uuid_bytes")]
+    Uuid([u8; 16]),
+    /// Nanoseconds since the Unix epoch, converting to and from `SystemTime`,
+    /// and `chrono::DateTime<Utc>` with the `chrono` feature.
+    #[serde(with = "int64")]
+    Timestamp(i64),
 }
 
 impl ZRpcDt {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ZRpcDt::Int8(_) => "Int8",
+            ZRpcDt::Int16(_) => "Int16",
+            ZRpcDt::Int32(_) => "Int32",
+            ZRpcDt::Int64(_) => "Int64",
+            ZRpcDt::UInt8(_) => "UInt8",
+            ZRpcDt::UInt16(_) => "UInt16",
+            ZRpcDt::UInt32(_) => "UInt32",
+            ZRpcDt::UInt64(_) => "UInt64",
+            ZRpcDt::Float32(_) => "Float32",
+            ZRpcDt::Float64(_) => "Float64",
+            ZRpcDt::String(_) => "String",
+            ZRpcDt::Bool(_) => "Bool",
+            ZRpcDt::Serialized(_) => "Serialized",
+            ZRpcDt::Ok => "Ok",
+            ZRpcDt::Bytes(_) => "Bytes",
+            ZRpcDt::Map(_) => "Map",
+            ZRpcDt::Null => "Null",
+            ZRpcDt::Array(_) => "Array",
+            ZRpcDt::Uuid(_) => "Uuid",
+            ZRpcDt::Timestamp(_) => "Timestamp",
+        }
+    }
+
     pub fn serialize<T: Serialize>(t: T) -> Self {
         Self::Serialized(bincode::serialize(&t).expect("Failed to serialize type"))
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn deserialize<T: for<'de> Deserialize<'de>>(&self) -> Result<T, ()> {
         if let Self::Serialized(bytes) = self {
             bincode::deserialize::<T>(bytes).map_err(|_| ())
@@ -124,4 +546,342 @@ impl ZRpcDt {
             Err(())
         }
     }
+
+    /// Fails the way encoding would if the value is nested deeper than
+    /// `MAX_DEPTH`, without encoding it.
+    pub(crate) fn check_depth(&self) -> Result<(), String> {
+        if self.within_depth(MAX_DEPTH) {
+            Ok(())
+        } else {
+            Err(depth::limit_exceeded())
+        }
+    }
+
+    fn within_depth(&self, depth: usize) -> bool {
+        match self {
+            ZRpcDt::Map(map) => depth > 0 && map.values().all(|dt| dt.within_depth(depth - 1)),
+            ZRpcDt::Array(items) => depth > 0 && items.iter().all(|dt| dt.within_depth(depth - 1)),
+            _ => true,
+        }
+    }
+}
+
+// Text formats such as JSON have no byte strings, so bytes are written as
+// base64 there.
+pub(crate) mod bytes {
+    use std::fmt::Formatter;
+
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde::{
+        Deserializer, Serializer,
+        de::{self, SeqAccess, Visitor},
+    };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            write!(f, "a byte array")
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            STANDARD.decode(v).map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+            while let Some(b) = seq.next_element()? {
+                bytes.push(b);
+            }
+
+            Ok(bytes)
+        }
+    }
+}
+
+// Text formats are often read by languages whose numbers are doubles, which
+// can't hold every 64-bit integer, so those are written as strings there.
+mod int64 {
+    use std::{fmt::Display, marker::PhantomData, str::FromStr};
+
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, Visitor},
+    };
+
+    pub fn serialize<T, S>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display + Serialize,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(v)
+        } else {
+            v.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + FromStr + TryFrom<i64> + TryFrom<u64>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(IntVisitor(PhantomData))
+        } else {
+            T::deserialize(deserializer)
+        }
+    }
+
+    struct IntVisitor<T>(PhantomData<T>);
+
+    impl<T> Visitor<'_> for IntVisitor<T>
+    where
+        T: FromStr + TryFrom<i64> + TryFrom<u64>,
+    {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "an integer or a string holding one")
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            T::try_from(v).map_err(|_| E::custom(format!("integer {} out of range", v)))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            T::try_from(v).map_err(|_| E::custom(format!("integer {} out of range", v)))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.parse()
+                .map_err(|_| E::custom(format!("invalid integer {:?}", v)))
+        }
+    }
+}
+
+// UUIDs are written in their usual hyphenated form in text formats, and as
+// their 16 bytes otherwise.
+mod uuid_bytes {
+    use std::fmt::{Formatter, Write};
+
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, Visitor},
+    };
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 16], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut text = String::with_capacity(36);
+
+            for (i, byte) in bytes.iter().enumerate() {
+                if matches!(i, 4 | 6 | 8 | 10) {
+                    text.push('-');
+                }
+                let _ = write!(text, "{:02x}", byte);
+            }
+
+            serializer.serialize_str(&text)
+        } else {
+            bytes.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 16], D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(UuidVisitor)
+        } else {
+            <[u8; 16]>::deserialize(deserializer)
+        }
+    }
+
+    struct UuidVisitor;
+
+    impl Visitor<'_> for UuidVisitor {
+        type Value = [u8; 16];
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            write!(f, "a hyphenated UUID")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<[u8; 16], E> {
+            let invalid = || E::custom(format!("invalid UUID {:?}", v));
+            let digits = v.replace('-', "");
+
+            if v.len() != 36 || digits.len() != 32 || !digits.is_ascii() {
+                return Err(invalid());
+            }
+
+            let mut bytes = [0; 16];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+            }
+
+            Ok(bytes)
+        }
+    }
+}
+
+// JSON has no NaN or infinities, so text formats get those as the strings
+// "NaN", "inf" and "-inf" instead.
+mod float {
+    use std::{fmt::Display, marker::PhantomData, str::FromStr};
+
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{self, Visitor},
+    };
+
+    pub trait Float: Copy + Display + FromStr + Serialize {
+        fn is_finite(self) -> bool;
+        fn from_f64(v: f64) -> Self;
+    }
+
+    impl Float for f32 {
+        fn is_finite(self) -> bool {
+            self.is_finite()
+        }
+
+        fn from_f64(v: f64) -> Self {
+            v as f32
+        }
+    }
+
+    impl Float for f64 {
+        fn is_finite(self) -> bool {
+            self.is_finite()
+        }
+
+        fn from_f64(v: f64) -> Self {
+            v
+        }
+    }
+
+    pub fn serialize<T: Float, S: Serializer>(v: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() && !v.is_finite() {
+            serializer.collect_str(v)
+        } else {
+            v.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Float + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(FloatVisitor(PhantomData))
+        } else {
+            T::deserialize(deserializer)
+        }
+    }
+
+    struct FloatVisitor<T>(PhantomData<T>);
+
+    impl<T: Float> Visitor<'_> for FloatVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a number, \"NaN\", \"inf\" or \"-inf\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+            Ok(T::from_f64(v))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            Ok(T::from_f64(v as f64))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            Ok(T::from_f64(v as f64))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.parse()
+                .map_err(|_| E::custom(format!("invalid number {:?}", v)))
+        }
+    }
+}
+
+// Nested values are (de)serialized recursively, so a hostile peer could send a
+// frame nested deep enough to overflow the stack. Every container variant
+// passes through here to keep the nesting level bounded.
+mod depth {
+    use std::cell::Cell;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de, ser};
+
+    use super::MAX_DEPTH;
+
+    thread_local! {
+        static DEPTH: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct DepthGuard;
+
+    impl DepthGuard {
+        fn enter() -> Option<Self> {
+            DEPTH.with(|d| {
+                if d.get() >= MAX_DEPTH {
+                    None
+                } else {
+                    d.set(d.get() + 1);
+                    Some(DepthGuard)
+                }
+            })
+        }
+    }
+
+    impl Drop for DepthGuard {
+        fn drop(&mut self) {
+            DEPTH.with(|d| d.set(d.get() - 1));
+        }
+    }
+
+    pub(super) fn limit_exceeded() -> String {
+        format!("ZRpcDt nesting exceeds the depth limit of {}", MAX_DEPTH)
+    }
+
+    pub fn serialize<T: Serialize, S: Serializer>(v: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        let _guard = DepthGuard::enter().ok_or_else(|| ser::Error::custom(limit_exceeded()))?;
+
+        v.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let _guard = DepthGuard::enter().ok_or_else(|| de::Error::custom(limit_exceeded()))?;
+
+        T::deserialize(deserializer)
+    }
 }
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    dt::ZRpcDt,
+    proc_error::ProcedureError,
+    req::{ZRpcHeaders, ZRpcReq},
+};
+
+/// Every frame carries the id of the call it belongs to, chosen by the
+/// client, so several calls can share a connection and be answered in any
+/// order.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ZRpcReqFrame {
+    Call(u32, ZRpcReq),
+    /// Opens a response stream, granting the server an initial number of
+    /// chunks it may send before waiting for more `Credit`.
+    Stream(u32, ZRpcReq, u32),
+    Credit(u32, u32),
+    Cancel(u32),
+    /// Several calls in one frame, answered by a single `Batch` frame.
+    Batch(u32, Vec<ZRpcReq>),
+    /// A heartbeat, answered with a `Pong` carrying the same id without
+    /// reaching any procedure.
+    Ping(u32),
+    /// A call the client expects no answer to. It carries no id, and the
+    /// server sends nothing back, not even errors.
+    Notify(ZRpcReq),
+    /// Opens an upload, whose data follows in `Data` frames once the server
+    /// grants `Credit`, then `UploadEnd`. Answered like `Call`.
+    Upload(u32, ZRpcReq),
+    Data(u32, #[serde(with = "super::dt::bytes")] Vec<u8>),
+    UploadEnd(u32),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ZRpcResFrame {
+    /// Answers a call, along with the headers the procedure set.
+    Result(u32, Result<ZRpcDt, ProcedureError>, ZRpcHeaders),
+    Chunk(u32, ZRpcDt),
+    End(u32, Result<(), ProcedureError>),
+    /// The results of a batch, in the order of its calls.
+    Batch(u32, Vec<Result<ZRpcDt, ProcedureError>>),
+    Pong(u32),
+    /// Lets the client send more `Data` frames of an upload.
+    Credit(u32, u32),
+}
+
+impl ZRpcResFrame {
+    /// The call this frame answers, if it isn't about the connection itself.
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            ZRpcResFrame::Result(id, _, _)
+            | ZRpcResFrame::Chunk(id, _)
+            | ZRpcResFrame::End(id, _)
+            | ZRpcResFrame::Batch(id, _)
+            | ZRpcResFrame::Credit(id, _) => Some(*id),
+            ZRpcResFrame::Pong(_) => None,
+        }
+    }
+}
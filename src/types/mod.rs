@@ -1,3 +1,6 @@
+pub mod ctx;
 pub mod dt;
+pub mod frame;
 pub mod proc_error;
 pub mod req;
+pub mod typed;
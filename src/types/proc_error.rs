@@ -1,14 +1,32 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::middleware::error::MiddlewareError;
+use crate::{middleware::error::MiddlewareError, types::dt::ZRpcDt};
 
 #[macro_export]
 macro_rules! proc_err {
     ($kind:ident) => {
         Err(ProcedureError::$kind)
     };
+    (Application, $code:expr, $message:expr) => {
+        Err(ProcedureError::application($code, $message))
+    };
+    (Application, $code:expr, $message:expr, $data:expr) => {{
+        use $crate::types::dt::ZRpcDtAuto;
+
+        Err(ProcedureError::application_with_data(
+            $code,
+            $message,
+            $data.to_zdt(),
+        ))
+    }};
+    ($kind:ident, $message:expr) => {
+        Err(ProcedureError::$kind($message.to_string()))
+    };
 }
 
 #[macro_export]
@@ -20,27 +38,116 @@ macro_rules! proc_ok {
     }};
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ProcedureError {
-    NotFound,
-    InvalidParameters,
-    Internal,
+    /// Nothing is registered under the called name, which is given in full,
+    /// e.g. `users.add`.
+    MethodNotFound(String),
+    /// The parameters don't fit the procedure, for the given reason.
+    BadParams(String),
+    /// The procedure failed unexpectedly. The message is meant for the
+    /// client, so it shouldn't leak server internals.
+    Internal(String),
     Middleware(String),
+    AfterMiddleware(String),
+    Unauthorized,
+    /// The server is shutting down and no longer takes new calls.
+    ShuttingDown,
+    /// A domain-specific failure; `code` is chosen by the application, and
+    /// `data` may hold structured details, such as the fields that failed
+    /// validation.
+    Application {
+        code: u32,
+        message: String,
+        data: Option<ZRpcDt>,
+    },
+    /// The procedure is running at its concurrency limit and too many calls
+    /// are already waiting for it. Clients see it as `ZRpcError::Overloaded`.
+    Overloaded,
+    /// The client made more calls than it is allowed to, and may try again
+    /// after the given time. Clients see it as `ZRpcError::RateLimited`.
+    RateLimited(Duration),
+}
+
+impl ProcedureError {
+    pub fn application(code: u32, message: impl Into<String>) -> Self {
+        ProcedureError::Application {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn application_with_data(code: u32, message: impl Into<String>, data: ZRpcDt) -> Self {
+        ProcedureError::Application {
+            code,
+            message: message.into(),
+            data: Some(data),
+        }
+    }
+
+    /// Whether the call was turned away before running, and may succeed
+    /// if issued again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProcedureError::ShuttingDown
+                | ProcedureError::Overloaded
+                | ProcedureError::RateLimited(_)
+        )
+    }
+
+    /// The application error code, if this is an `Application` error.
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            ProcedureError::Application { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The details attached to an `Application` error, if any.
+    pub fn data(&self) -> Option<&ZRpcDt> {
+        match self {
+            ProcedureError::Application { data, .. } => data.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 impl Display for ProcedureError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            ProcedureError::NotFound => write!(f, "NotFound"),
-            ProcedureError::InvalidParameters => write!(f, "InvalidParameters"),
-            ProcedureError::Internal => write!(f, "Internal"),
+            ProcedureError::MethodNotFound(proc) => write!(f, "MethodNotFound(\"{}\")", proc),
+            ProcedureError::BadParams(e) => write!(f, "BadParams(\"{}\")", e),
+            ProcedureError::Internal(e) => write!(f, "Internal(\"{}\")", e),
             ProcedureError::Middleware(e) => write!(f, "Middleware(\"{}\")", e),
+            ProcedureError::AfterMiddleware(e) => write!(f, "AfterMiddleware(\"{}\")", e),
+            ProcedureError::Unauthorized => write!(f, "Unauthorized"),
+            ProcedureError::ShuttingDown => write!(f, "ShuttingDown"),
+            ProcedureError::Application {
+                code,
+                message,
+                data: None,
+            } => write!(f, "Application({}): {}", code, message),
+            ProcedureError::Application {
+                code,
+                message,
+                data: Some(data),
+            } => write!(f, "Application({}): {} {:?}", code, message, data),
+            ProcedureError::Overloaded => write!(f, "Overloaded"),
+            ProcedureError::RateLimited(after) => write!(f, "RateLimited(retry after {:?})", after),
         }
     }
 }
 
+impl std::error::Error for ProcedureError {}
+
 impl From<MiddlewareError> for ProcedureError {
     fn from(err: MiddlewareError) -> Self {
-        ProcedureError::Middleware(err.0)
+        match err {
+            MiddlewareError::Rejected(e) => ProcedureError::Middleware(e),
+            MiddlewareError::Unauthorized => ProcedureError::Unauthorized,
+            MiddlewareError::RateLimited(after) => ProcedureError::RateLimited(after),
+        }
     }
 }
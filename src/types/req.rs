@@ -1,25 +1,78 @@
+use std::{collections::HashMap, time::Duration};
+
 use serde::{Deserialize, Serialize};
 
 use super::dt::ZRpcDt;
 
 #[macro_export]
 macro_rules! params {
-    ($($a:expr),*) => {{
+    ($($a:tt)*) => {{
         use libzrpc::types::dt::ZRpcDtAuto;
 
         let mut res: Vec<ZRpcDt> = vec![];
-        $(
-            res.push($a.to_zdt());
-        )*
+        $crate::__params!(@push res; $($a)*);
         res
     }};
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ZRpcReq(pub String, pub Vec<ZRpcDt>);
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __params {
+    (@push $res:ident;) => {};
+    (@push $res:ident; { $($map:tt)* } $(, $($rest:tt)*)?) => {
+        $res.push($crate::__params!(@map $($map)*));
+        $crate::__params!(@push $res; $($($rest)*)?);
+    };
+    (@push $res:ident; None $(, $($rest:tt)*)?) => {
+        $res.push($crate::types::dt::ZRpcDt::Null);
+        $crate::__params!(@push $res; $($($rest)*)?);
+    };
+    (@push $res:ident; $a:expr $(, $($rest:tt)*)?) => {
+        $res.push($a.to_zdt());
+        $crate::__params!(@push $res; $($($rest)*)?);
+    };
+    (@map $($entries:tt)*) => {{
+        let mut map = ::std::collections::HashMap::new();
+        $crate::__params!(@entry map; $($entries)*);
+        $crate::types::dt::ZRpcDt::Map(map)
+    }};
+    (@entry $map:ident;) => {};
+    (@entry $map:ident; $k:expr => { $($v:tt)* } $(, $($rest:tt)*)?) => {
+        $map.insert($k.to_string(), $crate::__params!(@map $($v)*));
+        $crate::__params!(@entry $map; $($($rest)*)?);
+    };
+    (@entry $map:ident; $k:expr => None $(, $($rest:tt)*)?) => {
+        $map.insert($k.to_string(), $crate::types::dt::ZRpcDt::Null);
+        $crate::__params!(@entry $map; $($($rest)*)?);
+    };
+    (@entry $map:ident; $k:expr => $v:expr $(, $($rest:tt)*)?) => {
+        $map.insert($k.to_string(), $v.to_zdt());
+        $crate::__params!(@entry $map; $($($rest)*)?);
+    };
+}
+
+/// Metadata sent alongside a call or its answer, such as a trace id.
+pub type ZRpcHeaders = HashMap<String, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZRpcReq {
+    pub proc: String,
+    pub params: Vec<ZRpcDt>,
+    pub auth_key: Option<String>,
+    /// How long the caller waits for the answer. The server derives the
+    /// call's deadline from it.
+    pub timeout: Option<Duration>,
+    pub headers: ZRpcHeaders,
+}
 
 impl ZRpcReq {
     pub fn new(proc: &str, params: Vec<ZRpcDt>) -> Self {
-        Self(proc.to_string(), params)
+        Self {
+            proc: proc.to_string(),
+            params,
+            auth_key: None,
+            timeout: None,
+            headers: ZRpcHeaders::new(),
+        }
     }
 }
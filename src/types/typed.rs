@@ -0,0 +1,84 @@
+use crate::log;
+
+use super::{
+    dt::{FromZRpcDt, IntoZRpcDt, ZRpcDt, ZRpcDtError},
+    proc_error::ProcedureError,
+};
+
+pub trait FromZRpcParams: Sized {
+    fn from_params(params: &[ZRpcDt]) -> Result<Self, ZRpcDtError>;
+}
+
+/// Converts the parameters of a call to `proc`, rejecting a mismatch with
+/// `ProcedureError::BadParams` saying which parameter didn't convert.
+#[doc(hidden)]
+pub fn parse_params<Args: FromZRpcParams>(
+    proc: &str,
+    params: &[ZRpcDt],
+) -> Result<Args, ProcedureError> {
+    Args::from_params(params).map_err(|e| {
+        log!("[ZRpcServer] '{}' rejected parameters: {}", proc, e);
+        ProcedureError::BadParams(e.to_string())
+    })
+}
+
+pub trait IntoProcResult {
+    fn into_proc_result(self) -> Result<ZRpcDt, ProcedureError>;
+}
+
+impl<T: IntoZRpcDt> IntoProcResult for T {
+    fn into_proc_result(self) -> Result<ZRpcDt, ProcedureError> {
+        Ok(self.into_zdt())
+    }
+}
+
+impl<T: IntoZRpcDt> IntoProcResult for Result<T, ProcedureError> {
+    fn into_proc_result(self) -> Result<ZRpcDt, ProcedureError> {
+        self.map(IntoZRpcDt::into_zdt)
+    }
+}
+
+pub trait TypedProc<Args, R>: Send + Sync + 'static {
+    fn call(&self, args: Args) -> R;
+}
+
+macro_rules! impl_typed_proc {
+    ($len:expr; $($t:ident: $i:tt),*) => {
+        impl<$($t: FromZRpcDt),*> FromZRpcParams for ($($t,)*) {
+            fn from_params(params: &[ZRpcDt]) -> Result<Self, ZRpcDtError> {
+                if params.len() != $len {
+                    return Err(ZRpcDtError(format!(
+                        "expected {} parameters, found {}",
+                        $len,
+                        params.len()
+                    )));
+                }
+
+                Ok(($(
+                    $t::from_zdt(&params[$i])
+                        .map_err(|e| ZRpcDtError(format!("parameter {}: {}", $i, e)))?,
+                )*))
+            }
+        }
+
+        impl<F, R, $($t),*> TypedProc<($($t,)*), R> for F
+        where
+            F: Fn($($t),*) -> R + Send + Sync + 'static,
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, ($($t,)*): ($($t,)*)) -> R {
+                self($($t),*)
+            }
+        }
+    };
+}
+
+impl_typed_proc!(0;);
+impl_typed_proc!(1; A: 0);
+impl_typed_proc!(2; A: 0, B: 1);
+impl_typed_proc!(3; A: 0, B: 1, C: 2);
+impl_typed_proc!(4; A: 0, B: 1, C: 2, D: 3);
+impl_typed_proc!(5; A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_typed_proc!(6; A: 0, B: 1, C: 2, D: 3, E: 4, G: 5);
+impl_typed_proc!(7; A: 0, B: 1, C: 2, D: 3, E: 4, G: 5, H: 6);
+impl_typed_proc!(8; A: 0, B: 1, C: 2, D: 3, E: 4, G: 5, H: 6, I: 7);
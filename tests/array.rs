@@ -0,0 +1,78 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{
+        dt::{FromZRpcDt, ZRpcDt},
+        proc_error::ProcedureError,
+    },
+};
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_typed_proc("sum", |ids: Vec<i32>| ids.iter().sum::<i32>())
+        .await;
+    server
+        .add_typed_proc("transpose", |rows: Vec<Vec<i32>>| {
+            (0..rows.first().map_or(0, Vec::len))
+                .map(|i| rows.iter().map(|row| row[i]).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        })
+        .await;
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn typed_lists_round_trip() {
+    let client = serve().await;
+
+    assert_eq!(
+        client.call("sum", params!(vec![1, 2, 3])).await.unwrap(),
+        ZRpcDt::Int32(6)
+    );
+    assert_eq!(
+        client
+            .call("sum", params!(Vec::<i32>::new()))
+            .await
+            .unwrap(),
+        ZRpcDt::Int32(0)
+    );
+
+    let res = client
+        .call("transpose", params!(vec![vec![1, 2], vec![3, 4]]))
+        .await
+        .unwrap();
+    assert_eq!(Vec::<Vec<i32>>::from_zdt(&res).unwrap(), [[1, 3], [2, 4]]);
+}
+
+#[tokio::test]
+async fn mixed_lists_round_trip_but_fail_typed_conversion() {
+    let client = serve().await;
+    let mixed = ZRpcDt::Array(vec![ZRpcDt::Int32(1), ZRpcDt::String("two".to_string())]);
+
+    let res = client.call("echo", vec![mixed.clone()]).await.unwrap();
+    assert_eq!(res, mixed);
+    let e = Vec::<i32>::from_zdt(&res).unwrap_err();
+    assert!(e.to_string().contains("[1]"), "{}", e);
+
+    let res = client.call("sum", vec![mixed]).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(e))) if e.contains("[1]")
+        ),
+        "{:?}",
+        res
+    );
+}
@@ -0,0 +1,49 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    middleware::auth::AuthMiddleware,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test]
+async fn only_known_keys_are_let_through() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_middleware(AuthMiddleware::new("old-key").with_key("new-key"))
+        .await;
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    for key in [
+        Some("old-key"),
+        Some("new-key"),
+        Some("new-kez"),
+        Some(""),
+        None,
+    ] {
+        let mut builder = ZRpcClient::builder(addr);
+        if let Some(key) = key {
+            builder = builder.auth_key(key);
+        }
+        let client = builder.build().await.unwrap();
+
+        let res = client.call("ping", params!()).await;
+        match key {
+            Some("old-key" | "new-key") => assert_eq!(res.unwrap(), ZRpcDt::Null),
+            _ => assert!(
+                matches!(res, Err(ZRpcError::Procedure(ProcedureError::Unauthorized))),
+                "{:?}: {:?}",
+                key,
+                res
+            ),
+        }
+    }
+}
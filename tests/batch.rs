@@ -0,0 +1,73 @@
+use std::{collections::HashMap, net::Ipv4Addr};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{
+        dt::{MAX_DEPTH, ZRpcDt},
+        proc_error::ProcedureError,
+    },
+};
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.add_typed_proc("add", |a: i32, b: i32| a + b).await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn malformed_entries_only_fail_themselves() {
+    let client = serve().await;
+    let too_deep = (0..=MAX_DEPTH).fold(ZRpcDt::Null, |inner, _| {
+        ZRpcDt::Map(HashMap::from([("inner".to_string(), inner)]))
+    });
+
+    let results = client
+        .call_batch(vec![
+            ("add", params!(1, 2)),
+            ("add", params!("one", 2)),
+            ("add", vec![ZRpcDt::Int32(1), too_deep]),
+            ("missing", params!()),
+            ("add", params!(3, 4)),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 5);
+    assert_eq!(results[0].as_ref().unwrap(), &ZRpcDt::Int32(3));
+    assert!(
+        matches!(
+            results[1],
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(_)))
+        ),
+        "{:?}",
+        results[1]
+    );
+    assert!(
+        matches!(&results[2], Err(ZRpcError::Serialization(e)) if e.contains("depth limit")),
+        "{:?}",
+        results[2]
+    );
+    assert!(
+        matches!(
+            results[3],
+            Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(_)))
+        ),
+        "{:?}",
+        results[3]
+    );
+    assert_eq!(results[4].as_ref().unwrap(), &ZRpcDt::Int32(7));
+}
+
+#[tokio::test]
+async fn empty_batch_has_no_results() {
+    let client = serve().await;
+
+    assert!(client.call_batch(vec![]).await.unwrap().is_empty());
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "blocking")]
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use libzrpc::{
+    blocking::BlockingZRpcClient, error::ZRpcError, params, server::ZRpcServer, types::dt::ZRpcDt,
+};
+
+/// Runs a server on a thread of its own, so the tests stay synchronous.
+fn serve() -> SocketAddr {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+            tx.send(server.local_addrs()[0]).unwrap();
+
+            server
+                .add_proc("add", |params| match params[..] {
+                    [ZRpcDt::Int32(a), ZRpcDt::Int32(b)] => Ok(ZRpcDt::Int32(a + b)),
+                    _ => Ok(ZRpcDt::Null),
+                })
+                .await;
+            server
+                .add_async_proc("sleep", |_, _| async {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    Ok(ZRpcDt::Null)
+                })
+                .await;
+            server.start().await
+        })
+    });
+
+    rx.recv().unwrap()
+}
+
+#[test]
+fn calls_work_without_a_runtime() {
+    let client = BlockingZRpcClient::new(serve()).unwrap();
+
+    assert!(client.is_connected());
+    assert_eq!(client.call("add", params!(2, 2)).unwrap(), ZRpcDt::Int32(4));
+    assert_eq!(client.call("add", params!(3, 4)).unwrap(), ZRpcDt::Int32(7));
+
+    let res = client.call_timeout("sleep", params!(), Duration::from_millis(50));
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+}
+
+#[test]
+fn clients_can_be_dropped_within_a_runtime() {
+    let client = BlockingZRpcClient::new(serve()).unwrap();
+    client.call("add", params!(1, 1)).unwrap();
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async move { drop(client) });
+}
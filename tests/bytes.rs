@@ -0,0 +1,57 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    types::dt::{FromZRpcDt, ZRpcDt},
+};
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    server
+        .add_typed_proc("len", |bytes: Vec<u8>| bytes.len() as u64)
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[test]
+fn vec_u8_is_sent_as_bytes() {
+    assert_eq!(params!(vec![1u8, 2, 3]), vec![ZRpcDt::Bytes(vec![1, 2, 3])]);
+    assert_eq!(params!(&[4u8, 5][..]), vec![ZRpcDt::Bytes(vec![4, 5])]);
+}
+
+#[tokio::test]
+async fn empty_bytes_round_trip() {
+    let client = serve().await;
+
+    let res = client
+        .call("echo", params!(Vec::<u8>::new()))
+        .await
+        .unwrap();
+    assert_eq!(res, ZRpcDt::Bytes(Vec::new()));
+    assert_eq!(
+        client.call("len", params!(Vec::<u8>::new())).await.unwrap(),
+        ZRpcDt::UInt64(0)
+    );
+}
+
+#[tokio::test]
+async fn multi_megabyte_bytes_round_trip() {
+    let client = serve().await;
+    let payload: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+    let res = client.call("echo", params!(payload.clone())).await.unwrap();
+    assert_eq!(Vec::<u8>::from_zdt(&res).unwrap(), payload);
+    assert_eq!(
+        client.call("len", params!(payload)).await.unwrap(),
+        ZRpcDt::UInt64(8 * 1024 * 1024)
+    );
+}
@@ -0,0 +1,42 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn clones_share_one_connection() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_async_proc("double", |_, params| async move {
+            match params.as_slice() {
+                [ZRpcDt::Int32(n)] => {
+                    // Answers arrive out of order.
+                    tokio::time::sleep(Duration::from_millis(*n as u64 % 7)).await;
+                    Ok(ZRpcDt::Int32(n * 2))
+                }
+                _ => Err(ProcedureError::BadParams("expected an integer".to_string())),
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let tasks: Vec<_> = (0..100)
+        .map(|n: i32| {
+            let client = client.clone();
+            tokio::spawn(async move { (n, client.call("double", params!(n)).await) })
+        })
+        .collect();
+
+    for task in tasks {
+        let (n, res) = task.await.unwrap();
+        assert_eq!(res.unwrap(), ZRpcDt::Int32(n * 2));
+    }
+}
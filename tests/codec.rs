@@ -0,0 +1,131 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    transport::codec::{Codec, ZRpcCodec},
+    types::{
+        dt::ZRpcDt,
+        frame::{ZRpcReqFrame, ZRpcResFrame},
+    },
+};
+use tokio::time::timeout;
+
+/// JSON under a tag of its own, standing in for a format the crate doesn't
+/// know of.
+struct Custom;
+
+fn serialization(e: serde_json::Error) -> ZRpcError {
+    ZRpcError::Serialization(e.to_string())
+}
+
+impl Codec for Custom {
+    fn tag(&self) -> u8 {
+        16
+    }
+
+    fn encode_request(&self, frame: &ZRpcReqFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        serde_json::to_writer(buf, frame).map_err(serialization)
+    }
+
+    fn decode_request(&self, bytes: &[u8]) -> Result<ZRpcReqFrame, ZRpcError> {
+        serde_json::from_slice(bytes).map_err(serialization)
+    }
+
+    fn encode_response(&self, frame: &ZRpcResFrame, buf: &mut Vec<u8>) -> Result<(), ZRpcError> {
+        serde_json::to_writer(buf, frame).map_err(serialization)
+    }
+
+    fn decode_response(&self, bytes: &[u8]) -> Result<ZRpcResFrame, ZRpcError> {
+        serde_json::from_slice(bytes).map_err(serialization)
+    }
+}
+
+async fn serve(codec: impl Codec + 'static) -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(codec);
+    server.add_typed_proc("add", |a: i64, b: i64| a + b).await;
+    server
+        .add_stream_proc("count", |_| {
+            Ok(futures::stream::iter((0..3).map(|i| Ok(ZRpcDt::Int32(i)))))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test]
+async fn custom_codecs_carry_calls_and_streams() {
+    let addr = serve(Custom).await;
+    let client = ZRpcClient::builder(addr)
+        .codec(Custom)
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        client.call("add", params!(2i64, 3i64)).await.unwrap(),
+        ZRpcDt::Int64(5)
+    );
+
+    let chunks: Vec<_> = client
+        .call_stream("count", params!())
+        .await
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(
+        chunks,
+        [ZRpcDt::Int32(0), ZRpcDt::Int32(1), ZRpcDt::Int32(2)]
+    );
+}
+
+/// Connects with `client` to a server using `server`, which must fail.
+async fn mismatch(server: ZRpcCodec, client: impl Codec + 'static) -> ZRpcError {
+    let addr = serve(server).await;
+
+    let res = timeout(
+        Duration::from_secs(1),
+        ZRpcClient::builder(addr).codec(client).build(),
+    )
+    .await
+    .expect("the mismatch wasn't noticed");
+
+    match res {
+        Ok(_) => panic!("connected with {:?}", server),
+        Err(e) => e,
+    }
+}
+
+#[tokio::test]
+async fn mismatched_codecs_fail_at_the_handshake() {
+    let e = mismatch(ZRpcCodec::Bincode, ZRpcCodec::Json).await;
+    assert!(
+        matches!(e, ZRpcError::CodecMismatch { local: 2, peer: 0 }),
+        "{:?}",
+        e
+    );
+    assert!(
+        e.to_string().contains("with Bincode, this side with Json"),
+        "{}",
+        e
+    );
+
+    let e = mismatch(ZRpcCodec::Json, Custom).await;
+    assert!(
+        matches!(e, ZRpcError::CodecMismatch { local: 16, peer: 2 }),
+        "{:?}",
+        e
+    );
+    assert!(e.to_string().contains("the codec tagged 16"), "{}", e);
+}
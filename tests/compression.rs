@@ -0,0 +1,111 @@
+#![cfg(any(feature = "lz4", feature = "zstd"))]
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use libzrpc::{
+    client::ZRpcClient, params, server::ZRpcServer, transport::compression::ZRpcCompression,
+    types::dt::ZRpcDt,
+};
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+async fn serve(compression: Option<ZRpcCompression>) -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    if let Some(algorithm) = compression {
+        server.set_compression(algorithm);
+    }
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+/// Forwards one connection to `server`, returning how many bytes went each
+/// way once it closes.
+async fn proxy(server: SocketAddr) -> (SocketAddr, JoinHandle<(u64, u64)>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let task = tokio::spawn(async move {
+        let (mut client, _) = listener.accept().await.unwrap();
+        let mut server = TcpStream::connect(server).await.unwrap();
+        copy_bidirectional(&mut client, &mut server).await.unwrap()
+    });
+
+    (addr, task)
+}
+
+/// Sends a megabyte of text there and back, returning the bytes written by
+/// the client and by the server.
+async fn round_trip(algorithm: ZRpcCompression, server: Option<ZRpcCompression>) -> (u64, u64) {
+    let (addr, traffic) = proxy(serve(server).await).await;
+    let client = ZRpcClient::builder(addr)
+        .compression(algorithm)
+        .build()
+        .await
+        .unwrap();
+
+    let text = "compressible ".repeat(80 * 1024);
+    let res = client.call("echo", params!(text.clone())).await.unwrap();
+    assert_eq!(res, ZRpcDt::String(text));
+
+    drop(client);
+    traffic.await.unwrap()
+}
+
+#[cfg(feature = "lz4")]
+#[tokio::test]
+async fn lz4_compresses_requests_and_responses() {
+    let (sent, received) = round_trip(ZRpcCompression::Lz4, Some(ZRpcCompression::Lz4)).await;
+
+    assert!(sent < 100 * 1024, "{} bytes sent", sent);
+    assert!(received < 100 * 1024, "{} bytes received", received);
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn zstd_compresses_requests_and_responses() {
+    let (sent, received) = round_trip(ZRpcCompression::Zstd, Some(ZRpcCompression::Zstd)).await;
+
+    assert!(sent < 100 * 1024, "{} bytes sent", sent);
+    assert!(received < 100 * 1024, "{} bytes received", received);
+}
+
+#[cfg(feature = "lz4")]
+#[tokio::test]
+async fn server_without_compression_answers_uncompressed() {
+    let (sent, received) = round_trip(ZRpcCompression::Lz4, None).await;
+
+    // It still decompresses what the client sends.
+    assert!(sent < 100 * 1024, "{} bytes sent", sent);
+    assert!(received > 1024 * 1024, "{} bytes received", received);
+}
+
+#[cfg(feature = "lz4")]
+#[tokio::test]
+async fn small_frames_are_sent_as_they_are() {
+    let (addr, traffic) = proxy(serve(Some(ZRpcCompression::Lz4)).await).await;
+    let client = ZRpcClient::builder(addr)
+        .compression(ZRpcCompression::Lz4)
+        .compression_threshold(usize::MAX)
+        .build()
+        .await
+        .unwrap();
+
+    let text = "compressible ".repeat(80 * 1024);
+    let res = client.call("echo", params!(text.clone())).await.unwrap();
+    assert_eq!(res, ZRpcDt::String(text));
+
+    // Only the request stays below the client's threshold.
+    drop(client);
+    let (sent, received) = traffic.await.unwrap();
+    assert!(sent > 1024 * 1024, "{} bytes sent", sent);
+    assert!(received < 100 * 1024, "{} bytes received", received);
+}
@@ -0,0 +1,93 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient, error::ZRpcError, params, server::ZRpcServer, types::dt::ZRpcDt,
+};
+use tokio::time::timeout;
+
+/// `report` and `feed` may each run once at a time, with one more call
+/// waiting. `report` answers after 300ms, `feed` never ends.
+async fn serve(running: Arc<AtomicUsize>) -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_async_proc("report", move |_, _| {
+            let running = running.clone();
+
+            async move {
+                running.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                Ok(ZRpcDt::Null)
+            }
+        })
+        .await;
+    server
+        .add_stream_proc("feed", |_| {
+            Ok(futures::stream::iter(0u64..).map(|n| Ok(ZRpcDt::UInt64(n))))
+        })
+        .await;
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    server.set_max_concurrency("report", 1, 1).await;
+    server.set_max_concurrency("feed", 1, 1).await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn calls_beyond_the_queue_are_overloaded() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let client = serve(running.clone()).await;
+
+    let (first, second) = {
+        let (a, b) = (client.clone(), client.clone());
+        (
+            tokio::spawn(async move { a.call("report", params!()).await }),
+            tokio::spawn(async move { b.call("report", params!()).await }),
+        )
+    };
+    while running.load(Ordering::SeqCst) == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let res = client.call("report", params!()).await;
+    assert!(matches!(res, Err(ZRpcError::Overloaded)), "{:?}", res);
+    assert!(res.unwrap_err().is_retryable());
+
+    // Other procedures don't wait for the limited one.
+    let res = timeout(Duration::from_millis(100), client.call("ping", params!())).await;
+    assert_eq!(res.expect("ping waited").unwrap(), ZRpcDt::Null);
+
+    assert_eq!(first.await.unwrap().unwrap(), ZRpcDt::Null);
+    assert_eq!(second.await.unwrap().unwrap(), ZRpcDt::Null);
+    assert_eq!(running.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn queued_stream_does_not_block_other_calls() {
+    let client = serve(Arc::new(AtomicUsize::new(0))).await;
+
+    let mut running = client.call_stream("feed", params!()).await.unwrap();
+    assert_eq!(running.next().await.unwrap().unwrap(), ZRpcDt::UInt64(0));
+    let _queued = client.call_stream("feed", params!()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let res = timeout(Duration::from_secs(1), client.call("ping", params!())).await;
+    assert_eq!(res.expect("ping waited").unwrap(), ZRpcDt::Null);
+
+    let mut overloaded = client.call_stream("feed", params!()).await.unwrap();
+    let res = overloaded.next().await.unwrap();
+    assert!(matches!(res, Err(ZRpcError::Overloaded)), "{:?}", res);
+}
@@ -0,0 +1,153 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    middleware::{Middleware, error::MiddlewareError},
+    params,
+    server::ZRpcServer,
+    types::{ctx::ZRpcContext, dt::ZRpcDt, req::ZRpcReq},
+};
+use tokio::{sync::mpsc, time::timeout};
+
+/// Attaches the tenant of every call to its context.
+struct Tenant;
+
+impl Middleware for Tenant {
+    fn before_call(&self, _: &mut ZRpcReq, ctx: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        ctx.insert("tenant", ZRpcDt::String("acme".to_string()));
+        Ok(())
+    }
+}
+
+/// `work` and `crunch` run until they are cancelled, then report how long
+/// that took. `crunch` polls from a blocking thread, which outlives the
+/// call's task when the connection closes.
+async fn serve(cancelled: mpsc::UnboundedSender<Duration>) -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let cancelled = Arc::new(cancelled);
+
+    server.add_middleware(Tenant).await;
+    server
+        .add_async_proc("work", {
+            let cancelled = cancelled.clone();
+
+            move |ctx, _| {
+                let cancelled = cancelled.clone();
+
+                async move {
+                    tokio::select! {
+                        _ = ctx.cancelled() => {
+                            assert!(ctx.is_cancelled());
+                            let _ = cancelled.send(ctx.elapsed());
+                        }
+                        _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+                    }
+                    Ok(ZRpcDt::Null)
+                }
+            }
+        })
+        .await;
+    server
+        .add_async_proc("crunch", {
+            let cancelled = cancelled.clone();
+
+            move |ctx, _| {
+                let cancelled = cancelled.clone();
+
+                async move {
+                    let _ = tokio::task::spawn_blocking(move || {
+                        while !ctx.is_cancelled() && ctx.elapsed() < Duration::from_secs(10) {
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                        let _ = cancelled.send(ctx.elapsed());
+                    })
+                    .await;
+                    Ok(ZRpcDt::Null)
+                }
+            }
+        })
+        .await;
+    server
+        .add_proc_with_ctx("tenant", |ctx, _| {
+            Ok(ctx.get("tenant").cloned().unwrap_or(ZRpcDt::Null))
+        })
+        .await;
+    server
+        .add_proc_with_ctx("remaining", |ctx, _| {
+            assert!(
+                ctx.deadline()
+                    .is_some_and(|deadline| deadline > Instant::now())
+            );
+            Ok(ZRpcDt::UInt64(
+                ctx.remaining().map_or(0, |left| left.as_millis() as u64),
+            ))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test]
+async fn handlers_see_the_client_disconnect() {
+    let (tx, mut cancelled) = mpsc::unbounded_channel();
+    let client = ZRpcClient::new(serve(tx).await).await.unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("crunch", params!()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(client);
+    call.abort();
+
+    let elapsed = timeout(Duration::from_secs(5), cancelled.recv())
+        .await
+        .expect("the handler kept running")
+        .unwrap();
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn handlers_see_their_deadline_pass() {
+    let (tx, mut cancelled) = mpsc::unbounded_channel();
+    let client = ZRpcClient::new(serve(tx).await).await.unwrap();
+
+    let res = client
+        .call_timeout("work", params!(), Duration::from_millis(100))
+        .await;
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+
+    let elapsed = timeout(Duration::from_secs(5), cancelled.recv())
+        .await
+        .expect("the handler kept running")
+        .unwrap();
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+    let res = client
+        .call_timeout("remaining", params!(), Duration::from_secs(2))
+        .await
+        .unwrap();
+    assert!(
+        matches!(res, ZRpcDt::UInt64(ms) if ms > 1000 && ms <= 2000),
+        "{:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn handlers_read_what_middleware_attached() {
+    let (tx, _) = mpsc::unbounded_channel();
+    let client = ZRpcClient::new(serve(tx).await).await.unwrap();
+
+    assert_eq!(
+        client.call("tenant", params!()).await.unwrap(),
+        ZRpcDt::String("acme".to_string())
+    );
+}
@@ -0,0 +1,147 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+use tokio::{sync::mpsc, time::timeout};
+
+/// The end of the chain, answering with the milliseconds left of the
+/// deadline it sees, and counting the calls that reach it.
+async fn downstream() -> (SocketAddr, Arc<AtomicUsize>) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    server
+        .add_proc_with_ctx("budget", {
+            let calls = calls.clone();
+
+            move |ctx, _| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(ctx
+                    .remaining()
+                    .map_or(ZRpcDt::Null, |left| ZRpcDt::UInt64(left.as_millis() as u64)))
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    (addr, calls)
+}
+
+/// The middle of the chain. `relay` waits the given milliseconds, then asks
+/// downstream for its budget, reporting what came back.
+async fn relay(
+    downstream: SocketAddr,
+    outcomes: mpsc::UnboundedSender<Result<ZRpcDt, ZRpcError>>,
+) -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let downstream = ZRpcClient::new(downstream).await.unwrap();
+
+    server
+        .add_async_proc("relay", move |ctx, params| {
+            let downstream = downstream.with_context(&ctx);
+            let outcomes = outcomes.clone();
+
+            async move {
+                if let [ZRpcDt::UInt64(wait)] = params[..] {
+                    tokio::time::sleep(Duration::from_millis(wait)).await;
+                }
+
+                let res = downstream.call("budget", params!()).await;
+                let answer = match &res {
+                    Ok(dt) => Ok(dt.clone()),
+                    Err(e) => Err(ProcedureError::Internal(e.to_string())),
+                };
+                let _ = outcomes.send(res);
+                answer
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test]
+async fn the_deadline_cascades_down_the_chain() {
+    let (addr, _) = downstream().await;
+    let (tx, _outcomes) = mpsc::unbounded_channel();
+    let client = ZRpcClient::new(relay(addr, tx).await).await.unwrap();
+
+    let res = client
+        .call_timeout("relay", params!(100u64), Duration::from_millis(500))
+        .await
+        .unwrap();
+    assert!(
+        matches!(res, ZRpcDt::UInt64(ms) if ms <= 400 && ms > 200),
+        "{:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn spent_budgets_fail_without_reaching_downstream() {
+    let (addr, calls) = downstream().await;
+    let (tx, mut outcomes) = mpsc::unbounded_channel();
+    let client = ZRpcClient::new(relay(addr, tx).await).await.unwrap();
+
+    // The relay outlives its caller's deadline before calling on.
+    let res = client
+        .call_timeout("relay", params!(300u64), Duration::from_millis(100))
+        .await;
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+
+    let outcome = timeout(Duration::from_secs(5), outcomes.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(outcome, Err(ZRpcError::TimeoutError)),
+        "{:?}",
+        outcome
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn deadlines_clamp_the_timeout_of_each_call() {
+    let (addr, calls) = downstream().await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let bounded = client.with_deadline(Instant::now() + Duration::from_millis(300));
+
+    let res = bounded
+        .call_timeout("budget", params!(), Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert!(matches!(res, ZRpcDt::UInt64(ms) if ms <= 300), "{:?}", res);
+
+    // A later deadline doesn't extend an earlier one.
+    let res = bounded
+        .with_deadline(Instant::now() + Duration::from_secs(10))
+        .call("budget", params!())
+        .await
+        .unwrap();
+    assert!(matches!(res, ZRpcDt::UInt64(ms) if ms <= 300), "{:?}", res);
+
+    let started = Instant::now();
+    let res = client
+        .with_deadline(Instant::now())
+        .call("budget", params!())
+        .await;
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+    assert!(started.elapsed() < Duration::from_millis(50));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, net::Ipv4Addr, time::Duration};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    transport::codec::ZRpcCodec,
+    types::{
+        dt::{MAX_DEPTH, ZRpcDt},
+        proc_error::ProcedureError,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+async fn serve(codec: ZRpcCodec) -> std::net::SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(codec);
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    server
+        .add_proc("too_deep", |_: &Vec<ZRpcDt>| Ok(nested(MAX_DEPTH + 1)))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+/// Maps nested `depth` levels deep.
+fn nested(depth: usize) -> ZRpcDt {
+    (0..depth).fold(ZRpcDt::Int32(1), |inner, _| {
+        ZRpcDt::Map(HashMap::from([("inner".to_string(), inner)]))
+    })
+}
+
+#[tokio::test]
+async fn struct_like_maps_round_trip() {
+    let client = ZRpcClient::new(serve(ZRpcCodec::Bincode).await)
+        .await
+        .unwrap();
+    let user = params!({
+        "name" => "Ada",
+        "age" => 36u8,
+        "address" => { "city" => "London", "zip" => None },
+    })
+    .remove(0);
+
+    assert_eq!(client.call("echo", vec![user.clone()]).await.unwrap(), user);
+}
+
+#[tokio::test]
+async fn values_up_to_the_depth_limit_are_accepted() {
+    let client = ZRpcClient::new(serve(ZRpcCodec::Bincode).await)
+        .await
+        .unwrap();
+
+    let value = nested(MAX_DEPTH);
+    assert_eq!(
+        client.call("echo", vec![value.clone()]).await.unwrap(),
+        value
+    );
+}
+
+#[tokio::test]
+async fn client_refuses_to_send_values_beyond_the_depth_limit() {
+    let client = ZRpcClient::new(serve(ZRpcCodec::Bincode).await)
+        .await
+        .unwrap();
+
+    let res = client.call("echo", vec![nested(MAX_DEPTH + 1)]).await;
+    assert!(
+        matches!(&res, Err(ZRpcError::Serialization(e)) if e.contains("depth limit")),
+        "{:?}",
+        res
+    );
+    assert!(client.call("echo", params!(1)).await.is_ok());
+}
+
+#[tokio::test]
+async fn response_beyond_the_depth_limit_fails_only_its_call() {
+    let client = ZRpcClient::new(serve(ZRpcCodec::Bincode).await)
+        .await
+        .unwrap();
+
+    let res = client.call("too_deep", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::Procedure(ProcedureError::Internal(_)))),
+        "{:?}",
+        res
+    );
+    assert!(client.call("echo", params!(1)).await.is_ok());
+}
+
+async fn send(stream: &mut TcpStream, frame: &[u8]) {
+    stream
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(frame).await.unwrap();
+}
+
+#[tokio::test]
+async fn server_drops_peers_sending_values_beyond_the_depth_limit() {
+    let addr = serve(ZRpcCodec::Json).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // A handshake for the JSON codec, then a call nested one level too deep,
+    // written by hand since no client would encode it.
+    send(&mut stream, b"\xFF{\"protocol\":1,\"codecs\":[2]}").await;
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut hello = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut hello).await.unwrap();
+
+    let value = format!(
+        "{}1{}",
+        "{\"Map\":{\"inner\":".repeat(MAX_DEPTH + 1),
+        "}}".repeat(MAX_DEPTH + 1)
+    );
+    let call = format!(
+        "\x02{{\"Call\":[0,{{\"proc\":\"echo\",\"params\":[{}],\"auth_key\":null,\"timeout\":null,\"headers\":{{}}}}]}}",
+        value
+    );
+    send(&mut stream, call.as_bytes()).await;
+
+    let mut buf = [0u8; 64];
+    let read = timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("the server kept the connection open");
+    assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+
+    // Only that connection is affected.
+    let client = ZRpcClient::builder(addr)
+        .codec(ZRpcCodec::Json)
+        .build()
+        .await
+        .unwrap();
+    assert!(client.call("echo", params!(1)).await.is_ok());
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "derive")]
+
+use std::{collections::HashMap, net::Ipv4Addr};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{
+        dt::{FromZRpcDt, IntoZRpcDt, ZRpcDeserialize, ZRpcDt, ZRpcSerialize},
+        proc_error::ProcedureError,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, ZRpcSerialize, ZRpcDeserialize)]
+struct Address {
+    city: String,
+    zip: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, ZRpcSerialize, ZRpcDeserialize)]
+struct User {
+    #[zrpc(rename = "userName")]
+    name: String,
+    age: u8,
+    email: Option<String>,
+    address: Address,
+    tags: Vec<String>,
+}
+
+fn ada() -> User {
+    User {
+        name: "Ada".to_string(),
+        age: 36,
+        email: None,
+        address: Address {
+            city: "London".to_string(),
+            zip: Some("N1".to_string()),
+        },
+        tags: vec!["admin".to_string()],
+    }
+}
+
+#[test]
+fn structs_convert_to_maps_keyed_by_field() {
+    let ZRpcDt::Map(map) = ada().into_zdt() else {
+        panic!("expected a map");
+    };
+
+    assert_eq!(map["userName"], ZRpcDt::String("Ada".to_string()));
+    assert_eq!(map["email"], ZRpcDt::Null);
+    assert!(!map.contains_key("name"));
+    assert_eq!(User::from_zdt(&ZRpcDt::Map(map)).unwrap(), ada());
+}
+
+#[test]
+fn missing_fields_are_named_unless_optional() {
+    let mut map = match ada().into_zdt() {
+        ZRpcDt::Map(map) => map,
+        dt => panic!("expected a map, got {:?}", dt),
+    };
+    map.remove("email");
+    assert_eq!(User::from_zdt(&ZRpcDt::Map(map.clone())).unwrap(), ada());
+
+    map.remove("age");
+    let e = User::from_zdt(&ZRpcDt::Map(map)).unwrap_err();
+    assert!(e.to_string().contains("missing field `age`"), "{}", e);
+
+    let e = User::from_zdt(&ZRpcDt::Map(HashMap::from([(
+        "userName".to_string(),
+        ZRpcDt::Int32(1),
+    )])))
+    .unwrap_err();
+    assert!(e.to_string().contains("userName"), "{}", e);
+}
+
+#[tokio::test]
+async fn derived_structs_round_trip_through_typed_procedures() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_typed_proc("birthday", |mut user: User| {
+            user.age += 1;
+            user
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let res = client.call("birthday", params!(ada())).await.unwrap();
+    assert_eq!(User::from_zdt(&res).unwrap(), User { age: 37, ..ada() });
+
+    let res = client
+        .call("birthday", params!({ "userName" => "Bob" }))
+        .await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(e))) if e.contains("missing field `age`")
+        ),
+        "{:?}",
+        res
+    );
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, net::Ipv4Addr};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params, proc_err,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test]
+async fn application_errors_reach_the_client_with_their_data() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("plain", |_: &Vec<ZRpcDt>| {
+            proc_err!(Application, 402, "insufficient funds")
+        })
+        .await;
+    server
+        .add_proc("detailed", |_: &Vec<ZRpcDt>| {
+            let details = HashMap::from([("limit".to_string(), 100)]);
+            proc_err!(Application, 403, "over the daily limit", details)
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let res = client.call("plain", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Application { code: 402, message, data: None })
+                if message == "insufficient funds"
+        ),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("detailed", params!()).await;
+    let Err(ZRpcError::Application {
+        code: 403,
+        data: Some(ZRpcDt::Map(data)),
+        ..
+    }) = res
+    else {
+        panic!("{:?}", res);
+    };
+    assert_eq!(data.get("limit"), Some(&ZRpcDt::Int32(100)));
+}
+
+#[tokio::test]
+async fn procedure_errors_keep_their_variant() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("bad", |_: &Vec<ZRpcDt>| {
+            proc_err!(BadParams, "expected an integer")
+        })
+        .await;
+    server
+        .add_proc("internal", |_: &Vec<ZRpcDt>| {
+            proc_err!(Internal, "disk full")
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let res = client.call("missing", params!()).await;
+    assert!(
+        matches!(
+            res,
+            Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(_)))
+        ),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("bad", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(e))) if e == "expected an integer"
+        ),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("internal", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::Internal(e))) if e == "disk full"
+        ),
+        "{:?}",
+        res
+    );
+}
@@ -0,0 +1,112 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::{ZRpcServer, service::ZRpcService},
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+async fn upstream() -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_typed_proc("whoami", || "upstream".to_string())
+        .await;
+    server.add_typed_proc("add", |a: i32, b: i32| a + b).await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+/// A gateway answering `whoami` itself and forwarding everything else.
+async fn gateway() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let upstream = ZRpcClient::new(upstream().await).await.unwrap();
+
+    server
+        .add_typed_proc("whoami", || "gateway".to_string())
+        .await;
+    let mut users = ZRpcService::new();
+    users.add_typed_proc("count", || 3u32);
+    server.add_service("users", users).await;
+    server
+        .set_fallback(move |_ctx, name, params| {
+            let upstream = upstream.clone();
+
+            async move {
+                if name.starts_with("internal.") {
+                    return Err(ProcedureError::Unauthorized);
+                }
+                upstream.call(&name, params).await.map_err(|e| match e {
+                    ZRpcError::Procedure(e) => e,
+                    e => ProcedureError::Internal(e.to_string()),
+                })
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn registered_procedures_take_precedence() {
+    let client = gateway().await;
+
+    assert_eq!(
+        client.call("whoami", params!()).await.unwrap(),
+        ZRpcDt::String("gateway".to_string())
+    );
+    assert_eq!(
+        client.call("users.count", params!()).await.unwrap(),
+        ZRpcDt::UInt32(3)
+    );
+}
+
+#[tokio::test]
+async fn unknown_names_reach_the_fallback() {
+    let client = gateway().await;
+
+    assert_eq!(
+        client.call("add", params!(2, 3)).await.unwrap(),
+        ZRpcDt::Int32(5)
+    );
+
+    let chunks: Vec<_> = client
+        .call_stream("add", params!(1, 1))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert!(
+        matches!(&chunks[..], [Ok(ZRpcDt::Int32(2))]),
+        "{:?}",
+        chunks
+    );
+
+    // Upstream doesn't know it either, and says so with the full name.
+    let res = client.call("users.remove", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(name))) if name == "users.remove"
+        ),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("internal.drop", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::Unauthorized))
+        ),
+        "{:?}",
+        res
+    );
+}
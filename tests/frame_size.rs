@@ -0,0 +1,87 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use libzrpc::{
+    client::ZRpcClient, error::ZRpcError, params, server::ZRpcServer, types::dt::ZRpcDt,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+async fn serve(mut server: ZRpcServer, size: usize) -> std::net::SocketAddr {
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("bytes", move |_: &Vec<ZRpcDt>| {
+            Ok(ZRpcDt::Bytes(vec![0; size]))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test]
+async fn server_closes_connection_on_oversized_length_prefix() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    server.set_max_frame_size(1024);
+    let addr = serve(server, 0).await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(&0x7FFF_FFFFu32.to_be_bytes())
+        .await
+        .unwrap();
+
+    // Had the server allocated a buffer for the announced frame, it would
+    // be waiting for its 2 GiB to arrive instead of hanging up.
+    let mut buf = [0u8; 64];
+    let read = timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("the server kept the connection open");
+    assert!(matches!(read, Ok(0) | Err(_)), "{:?}", read);
+}
+
+#[tokio::test]
+async fn client_rejects_oversized_response() {
+    let server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = serve(server, 64 * 1024).await;
+
+    let client = ZRpcClient::builder(addr)
+        .max_frame_size(1024)
+        .build()
+        .await
+        .unwrap();
+
+    let res = client.call("bytes", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::FrameTooLarge(1024))),
+        "{:?}",
+        res
+    );
+}
+
+#[cfg(feature = "lz4")]
+#[tokio::test]
+async fn client_rejects_response_oversized_once_decompressed() {
+    use libzrpc::transport::compression::ZRpcCompression;
+
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    server.set_compression(ZRpcCompression::Lz4);
+    // Zeros compress to well below the limit.
+    let addr = serve(server, 64 * 1024).await;
+
+    let client = ZRpcClient::builder(addr)
+        .max_frame_size(4096)
+        .build()
+        .await
+        .unwrap();
+
+    let res = client.call("bytes", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::FrameTooLarge(4096))),
+        "{:?}",
+        res
+    );
+}
@@ -0,0 +1,93 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use libzrpc::{server::ZRpcServer, transport::codec::ZRpcCodec, types::dt::ZRpcDt};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+async fn serve() -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(ZRpcCodec::Json);
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+const HELLO: u8 = 0xFF;
+const JSON: u8 = 2;
+const PING: &str =
+    r#"{"Call":[0,{"proc":"ping","params":[],"auth_key":null,"timeout":null,"headers":{}}]}"#;
+
+async fn send(stream: &mut TcpStream, tag: u8, text: &str) {
+    stream
+        .write_all(&(1 + text.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&[tag]).await.unwrap();
+    stream.write_all(text.as_bytes()).await.unwrap();
+}
+
+/// The next frame as text after its tag, or `None` once the server has
+/// closed the connection.
+async fn receive(stream: &mut TcpStream) -> Option<String> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.ok()?;
+    let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).await.ok()?;
+
+    Some(String::from_utf8(frame[1..].to_vec()).unwrap())
+}
+
+async fn hello(hello: &str) -> (TcpStream, Option<String>) {
+    let mut stream = TcpStream::connect(serve().await).await.unwrap();
+    send(&mut stream, HELLO, hello).await;
+    let answer = receive(&mut stream).await;
+
+    (stream, answer)
+}
+
+#[tokio::test]
+async fn connections_must_open_with_a_handshake() {
+    let mut stream = TcpStream::connect(serve().await).await.unwrap();
+    send(&mut stream, JSON, PING).await;
+
+    assert_eq!(receive(&mut stream).await, None);
+}
+
+#[tokio::test]
+async fn newer_clients_are_answered_with_the_newest_common_version() {
+    let (mut stream, answer) =
+        hello(r#"{"protocol":99,"codecs":[2],"version":"9.0.0","unknown":[1,2]}"#).await;
+    let answer = answer.unwrap();
+    assert!(answer.contains(r#""protocol":1"#), "{}", answer);
+    assert!(!answer.contains("error"), "{}", answer);
+
+    send(&mut stream, JSON, PING).await;
+    assert_eq!(
+        receive(&mut stream).await.unwrap(),
+        r#"{"Result":[0,{"Ok":"Null"},{}]}"#
+    );
+}
+
+#[tokio::test]
+async fn incompatible_clients_are_told_why() {
+    for (client, reason) in [
+        (r#"{"protocol":0,"codecs":[2]}"#, "speaks protocol 0"),
+        (
+            r#"{"protocol":1,"codecs":[0]}"#,
+            "can't encode frames with Json",
+        ),
+    ] {
+        let (mut stream, answer) = hello(client).await;
+        let answer = answer.unwrap();
+        assert!(answer.contains(reason), "{}", answer);
+
+        assert_eq!(receive(&mut stream).await, None);
+    }
+}
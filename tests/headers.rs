@@ -0,0 +1,104 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    middleware::{Middleware, error::MiddlewareError},
+    params,
+    server::ZRpcServer,
+    types::{
+        ctx::ZRpcContext,
+        dt::ZRpcDt,
+        proc_error::ProcedureError,
+        req::{ZRpcHeaders, ZRpcReq},
+    },
+};
+
+/// Starts a trace for calls that come without one, and echoes it back.
+struct Tracing;
+
+impl Middleware for Tracing {
+    fn before_call(&self, req: &mut ZRpcReq, _: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        req.headers
+            .entry("trace-id".to_string())
+            .or_insert_with(|| "fresh".to_string());
+        req.headers.remove("authorization");
+        Ok(())
+    }
+
+    fn after_call(
+        &self,
+        _: &ZRpcReq,
+        ctx: &ZRpcContext,
+        _: &mut Result<ZRpcDt, ProcedureError>,
+    ) -> Result<(), MiddlewareError> {
+        if let Some(trace) = ctx.header("trace-id") {
+            ctx.set_response_header("trace-id", trace);
+        }
+        Ok(())
+    }
+}
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.add_middleware(Tracing).await;
+    server
+        .add_proc_with_ctx("whoami", |ctx, _| {
+            assert!(ctx.header("authorization").is_none());
+            ctx.set_response_header("served-by", "test");
+            Ok(ctx
+                .header("tenant")
+                .map_or(ZRpcDt::Null, |tenant| ZRpcDt::String(tenant.to_string())))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+fn headers(pairs: &[(&str, &str)]) -> ZRpcHeaders {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn headers_travel_both_ways() {
+    let client = serve().await;
+
+    let (res, answered) = client
+        .call_with_headers(
+            "whoami",
+            params!(),
+            headers(&[("tenant", "acme"), ("trace-id", "abc")]),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res, ZRpcDt::String("acme".to_string()));
+    assert_eq!(
+        answered,
+        headers(&[("served-by", "test"), ("trace-id", "abc")])
+    );
+}
+
+#[tokio::test]
+async fn middleware_rewrites_headers_before_the_handler() {
+    let client = serve().await;
+
+    let (res, answered) = client
+        .call_with_headers("whoami", params!(), headers(&[("authorization", "secret")]))
+        .await
+        .unwrap();
+
+    assert_eq!(res, ZRpcDt::Null);
+    assert_eq!(answered["trace-id"], "fresh");
+
+    // Plain calls carry no headers at all.
+    assert_eq!(
+        client.call("whoami", params!()).await.unwrap(),
+        ZRpcDt::Null
+    );
+}
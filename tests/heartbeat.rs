@@ -0,0 +1,137 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use libzrpc::{
+    client::{ConnectionState, ZRpcClient},
+    error::ZRpcError,
+    metrics::Metrics,
+    params,
+    server::ZRpcServer,
+    types::dt::ZRpcDt,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+struct CountCalls(Arc<AtomicUsize>);
+
+impl Metrics for CountCalls {
+    fn on_call_start(&self, _: &str) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+async fn serve(calls: Arc<AtomicUsize>) -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_metrics(CountCalls(calls));
+    server
+        .add_async_proc("wait", |_, _| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(ZRpcDt::Null)
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+/// Copies bytes until `frozen` is set, then holds on to them without
+/// closing anything, like a NAT gateway that forgot the connection.
+async fn forward(
+    mut from: impl AsyncRead + Unpin,
+    mut to: impl AsyncWrite + Unpin,
+    frozen: Arc<AtomicBool>,
+) {
+    let mut buf = vec![0u8; 64 * 1024];
+
+    loop {
+        let n = from.read(&mut buf).await.unwrap_or(0);
+        while frozen.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        if n == 0 || to.write_all(&buf[..n]).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Forwards a single connection to `server` until frozen.
+async fn proxy(server: SocketAddr, frozen: Arc<AtomicBool>) -> SocketAddr {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (client, _) = listener.accept().await.unwrap();
+        let server = TcpStream::connect(server).await.unwrap();
+        let (client_read, client_write) = client.into_split();
+        let (server_read, server_write) = server.into_split();
+
+        tokio::join!(
+            forward(client_read, server_write, frozen.clone()),
+            forward(server_read, client_write, frozen),
+        );
+    });
+
+    addr
+}
+
+#[tokio::test]
+async fn pings_keep_an_idle_connection_without_reaching_procedures() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let addr = serve(calls.clone()).await;
+    let client = ZRpcClient::builder(addr)
+        .heartbeat(Duration::from_millis(20), Duration::from_millis(200))
+        .build()
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(client.is_connected());
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn silent_connection_is_closed_once_pongs_stop() {
+    let frozen = Arc::new(AtomicBool::new(false));
+    let addr = proxy(serve(Arc::default()).await, frozen.clone()).await;
+    let client = ZRpcClient::builder(addr)
+        .heartbeat(Duration::from_millis(50), Duration::from_millis(100))
+        .timeout(Duration::from_secs(10))
+        .build()
+        .await
+        .unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("wait", params!()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(client.is_connected());
+
+    frozen.store(true, Ordering::SeqCst);
+    let mut states = client.state_changes();
+    timeout(
+        Duration::from_secs(2),
+        states.wait_for(|state| *state == ConnectionState::Disconnected),
+    )
+    .await
+    .expect("the connection was kept")
+    .unwrap();
+
+    // The call waiting on the connection fails long before its timeout.
+    let res = timeout(Duration::from_secs(1), call)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(res, Err(ZRpcError::Io(_))), "{:?}", res);
+}
@@ -0,0 +1,94 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::{ZRpcClient, interceptor::Interceptor},
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq},
+};
+
+#[derive(Clone, Default)]
+struct Balance {
+    entered: Arc<AtomicUsize>,
+    left: Arc<AtomicUsize>,
+}
+
+impl Balance {
+    fn get(&self) -> (usize, usize) {
+        (
+            self.entered.load(Ordering::SeqCst),
+            self.left.load(Ordering::SeqCst),
+        )
+    }
+}
+
+impl Interceptor for Balance {
+    fn before_call(&self, _: &mut ZRpcReq) -> Result<(), ZRpcError> {
+        self.entered.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn after_call(&self, _: &ZRpcReq, res: &mut Result<ZRpcDt, ZRpcError>) {
+        self.left.fetch_add(1, Ordering::SeqCst);
+
+        if let Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(_))) = res {
+            *res = Err(ZRpcError::Rejected("no such method".to_string()));
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_call_leaves_the_interceptors_it_entered() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    server
+        .add_stream_proc("count", |_: &Vec<ZRpcDt>| {
+            Ok(futures::stream::iter((0..3).map(|n| Ok(ZRpcDt::Int32(n)))))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let balance = Balance::default();
+    let client = ZRpcClient::builder(addr)
+        .interceptor(balance.clone())
+        .build()
+        .await
+        .unwrap();
+
+    client.call("ping", params!()).await.unwrap();
+    assert_eq!(balance.get(), (1, 1));
+
+    client.notify("ping", params!()).await.unwrap();
+    assert_eq!(balance.get(), (2, 2));
+
+    let chunks: Vec<_> = client
+        .call_stream("count", params!())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(balance.get(), (3, 3));
+
+    let mut missing = client.call_stream("missing", params!()).await.unwrap();
+    let res = missing.next().await;
+    assert!(
+        matches!(&res, Some(Err(ZRpcError::Rejected(e))) if e == "no such method"),
+        "{:?}",
+        res
+    );
+    assert!(missing.next().await.is_none());
+    assert_eq!(balance.get(), (4, 4));
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+use libzrpc::{
+    client::ZRpcClient, params, server::ZRpcServer, transport::codec::ZRpcCodec, types::dt::ZRpcDt,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+async fn serve() -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(ZRpcCodec::Json);
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| {
+            Ok(ZRpcDt::Array(params.clone()))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+async fn send(stream: &mut TcpStream, frame: &[u8]) {
+    stream
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(frame).await.unwrap();
+}
+
+/// Reads a frame, returning its tag and the rest as text.
+async fn receive(stream: &mut TcpStream) -> (u8, String) {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).await.unwrap();
+    let text = String::from_utf8(frame.split_off(1)).unwrap();
+    (frame[0], text)
+}
+
+#[tokio::test]
+async fn values_round_trip_exactly() {
+    let client = ZRpcClient::builder(serve().await)
+        .codec(ZRpcCodec::Json)
+        .build()
+        .await
+        .unwrap();
+
+    let values = vec![
+        ZRpcDt::Int32(5),
+        ZRpcDt::Float64(5.0),
+        ZRpcDt::Float32(f32::NEG_INFINITY),
+        ZRpcDt::Int64(i64::MIN),
+        ZRpcDt::UInt64(u64::MAX),
+        ZRpcDt::Timestamp(1 << 60),
+        ZRpcDt::Bytes(vec![0, 1, 255]),
+        ZRpcDt::Uuid([7; 16]),
+        ZRpcDt::Map(HashMap::from([("nested".to_string(), ZRpcDt::Null)])),
+        ZRpcDt::Ok,
+    ];
+
+    assert_eq!(
+        client.call("echo", values.clone()).await.unwrap(),
+        ZRpcDt::Array(values)
+    );
+    let res = client.call("echo", params!(f64::NAN)).await.unwrap();
+    assert!(
+        matches!(&res, ZRpcDt::Array(values) if matches!(values[..], [ZRpcDt::Float64(f)] if f.is_nan())),
+        "{:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn frames_can_be_written_and_read_by_hand() {
+    let mut stream = TcpStream::connect(serve().await).await.unwrap();
+    send(&mut stream, b"\xFF{\"protocol\":1,\"codecs\":[2]}").await;
+    let (tag, hello) = receive(&mut stream).await;
+    assert_eq!(tag, 0xFF);
+    assert!(hello.contains("\"codecs\":[2]"), "{}", hello);
+
+    let call = r#"{"Call":[7,{"proc":"echo","params":[{"Int32":5},{"Float64":5.0},{"Int64":"9007199254740993"},{"Bytes":"AAH/"}],"auth_key":null,"timeout":null,"headers":{}}]}"#;
+    send(&mut stream, format!("\x02{}", call).as_bytes()).await;
+
+    let (tag, answer) = receive(&mut stream).await;
+    assert_eq!(tag, 2);
+    assert_eq!(
+        answer,
+        r#"{"Result":[7,{"Ok":{"Array":[{"Int32":5},{"Float64":5.0},{"Int64":"9007199254740993"},{"Bytes":"AAH/"}]}},{}]}"#
+    );
+}
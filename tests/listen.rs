@@ -0,0 +1,57 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use libzrpc::{client::ZRpcClient, params, server::ZRpcServer, types::dt::ZRpcDt};
+
+async fn serve(mut server: ZRpcServer) {
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    tokio::spawn(async move { server.start().await });
+}
+
+async fn ping(addr: impl Into<SocketAddr>) -> bool {
+    match ZRpcClient::new(addr.into()).await {
+        Ok(client) => client.call("ping", params!()).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// A port nothing listens on, on either stack.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind((Ipv6Addr::UNSPECIFIED, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+async fn both_stacks_share_a_port() {
+    let port = free_port();
+    let server = ZRpcServer::bind(&[
+        (Ipv4Addr::LOCALHOST, port).into(),
+        (Ipv6Addr::LOCALHOST, port).into(),
+    ])
+    .await
+    .unwrap();
+    serve(server).await;
+
+    assert!(ping((Ipv4Addr::LOCALHOST, port)).await);
+    assert!(ping((Ipv6Addr::LOCALHOST, port)).await);
+}
+
+#[tokio::test]
+async fn ipv6_listener_stays_dual_stack_next_to_ipv4_on_another_port() {
+    let (v6, v4) = (free_port(), free_port());
+    let server = ZRpcServer::bind(&[
+        (Ipv6Addr::UNSPECIFIED, v6).into(),
+        (Ipv4Addr::LOCALHOST, v4).into(),
+    ])
+    .await
+    .unwrap();
+    serve(server).await;
+
+    assert!(ping((Ipv4Addr::LOCALHOST, v6)).await);
+    assert!(ping((Ipv6Addr::LOCALHOST, v6)).await);
+    assert!(ping((Ipv4Addr::LOCALHOST, v4)).await);
+}
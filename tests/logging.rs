@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::{Mutex, Once},
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    middleware::logging::{Level, LoggingMiddleware},
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+use log::{Log, Metadata, Record};
+
+/// Collects the records of this test binary, every test reading those of
+/// procedures only it calls.
+struct Capture(Mutex<Vec<(Level, String)>>);
+
+static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+impl Log for Capture {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+fn logged(proc: &str) -> Vec<(Level, String)> {
+    CAPTURE
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, line)| line.starts_with(&format!("{}(", proc)))
+        .cloned()
+        .collect()
+}
+
+async fn serve(logging: LoggingMiddleware) -> ZRpcClient {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.add_middleware(logging).await;
+    for name in ["login", "upload"] {
+        server
+            .add_proc(name, |params: &Vec<ZRpcDt>| match params.first() {
+                Some(ZRpcDt::String(user)) if user == "mallory" => {
+                    Err(ProcedureError::Unauthorized)
+                }
+                _ => Ok(ZRpcDt::Ok),
+            })
+            .await;
+    }
+    server
+        .add_stream_proc("tail", |_| {
+            Ok(futures::stream::iter([Ok(ZRpcDt::Int32(1))]))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn calls_are_logged_with_secrets_redacted() {
+    let client = serve(
+        LoggingMiddleware::new(Level::Warn)
+            .redact_param(1)
+            .redact_header("Authorization"),
+    )
+    .await;
+
+    client
+        .call_with_headers(
+            "login",
+            params!("ada", "SECRET_KEY"),
+            HashMap::from([
+                (
+                    "authorization".to_string(),
+                    "Bearer SECRET_TOKEN".to_string(),
+                ),
+                ("trace-id".to_string(), "abc".to_string()),
+            ]),
+        )
+        .await
+        .unwrap();
+    client
+        .call("login", params!("mallory", "hunter2"))
+        .await
+        .unwrap_err();
+
+    let lines = logged("login");
+    assert_eq!(lines.len(), 2, "{:?}", lines);
+    assert!(lines.iter().all(|(level, _)| *level == Level::Warn));
+
+    let (_, ok) = &lines[0];
+    assert!(
+        ok.starts_with(r#"login(String("ada"), ***) [authorization: ***, trace-id: abc] ok in "#),
+        "{}",
+        ok
+    );
+    let (_, failed) = &lines[1];
+    assert!(failed.contains("***) failed: "), "{}", failed);
+    for (_, line) in &lines {
+        assert!(
+            !line.contains("SECRET") && !line.contains("hunter2"),
+            "{}",
+            line
+        );
+    }
+}
+
+#[tokio::test]
+async fn blobs_are_summarized_and_streams_logged_once_done() {
+    let client = serve(LoggingMiddleware::new(Level::Info)).await;
+
+    client
+        .call("upload", vec![ZRpcDt::Bytes(vec![0; 4096])])
+        .await
+        .unwrap();
+    let lines = logged("upload");
+    assert!(
+        lines[0].1.starts_with("upload(Bytes(4096 bytes)) ok in "),
+        "{:?}",
+        lines
+    );
+
+    let chunks: Vec<_> = client
+        .call_stream("tail", params!())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks.len(), 1);
+    let lines = logged("tail");
+    assert!(
+        lines.len() == 1 && lines[0].1.starts_with("tail() ok in "),
+        "{:?}",
+        lines
+    );
+}
@@ -0,0 +1,79 @@
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+async fn serve() -> ZRpcServer {
+    let mut server = ZRpcServer::new_in_memory();
+
+    server.add_typed_proc("add", |a: i32, b: i32| a + b).await;
+    server
+        .add_stream_proc("count", |params| match params.as_slice() {
+            [ZRpcDt::UInt64(n)] => Ok(futures::stream::iter(0..*n).map(|i| Ok(ZRpcDt::UInt64(i)))),
+            _ => Err(ProcedureError::BadParams("expected a count".to_string())),
+        })
+        .await;
+
+    server
+}
+
+#[tokio::test]
+async fn in_memory_client_calls_without_a_socket() {
+    let mut server = serve().await;
+    assert!(server.local_addrs().is_empty());
+    let handle = server.handle();
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new_in_memory(&handle).await.unwrap();
+    assert_eq!(
+        client.call("add", params!(2, 3)).await.unwrap(),
+        ZRpcDt::Int32(5)
+    );
+
+    let chunks: Vec<_> = client
+        .call_stream("count", params!(3u64))
+        .await
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(
+        chunks,
+        [ZRpcDt::UInt64(0), ZRpcDt::UInt64(1), ZRpcDt::UInt64(2)]
+    );
+}
+
+#[tokio::test]
+async fn each_in_memory_client_gets_its_own_connection() {
+    let mut server = serve().await;
+    let handle = server.handle();
+    tokio::spawn(async move { server.start().await });
+
+    let first = ZRpcClient::new_in_memory(&handle).await.unwrap();
+    let second = ZRpcClient::new_in_memory(&handle).await.unwrap();
+    drop(first);
+
+    assert_eq!(
+        second.call("add", params!(1, 1)).await.unwrap(),
+        ZRpcDt::Int32(2)
+    );
+}
+
+#[tokio::test]
+async fn server_serves_any_stream() {
+    let mut server = serve().await;
+    let handle = server.handle();
+    tokio::spawn(async move { server.start().await });
+
+    let (client_end, server_end) = tokio::io::duplex(64 * 1024);
+    handle.serve(server_end).unwrap();
+
+    let client = ZRpcClient::from_stream(client_end).await.unwrap();
+    assert_eq!(
+        client.call("add", params!(4, 5)).await.unwrap(),
+        ZRpcDt::Int32(9)
+    );
+}
@@ -0,0 +1,127 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    metrics::{CallOutcome, Metrics},
+    params,
+    server::ZRpcServer,
+    types::dt::ZRpcDt,
+};
+
+/// Writes down every hook it sees, in order.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Recorder {
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: String) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+/// Waits until `recorder` has seen `events`, which may come in late, as
+/// connections are closed by tasks of their own.
+async fn expect(recorder: &Recorder, events: &[&str]) {
+    let seen = tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.events().len() < events.len() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await;
+
+    assert!(seen.is_ok(), "{:?}", recorder.events());
+    assert_eq!(recorder.events(), events);
+}
+
+impl Metrics for Recorder {
+    fn on_connection_open(&self) {
+        self.record("open".to_string());
+    }
+
+    fn on_connection_close(&self) {
+        self.record("close".to_string());
+    }
+
+    fn on_call_start(&self, proc: &str) {
+        self.record(format!("start {}", proc));
+    }
+
+    fn on_call_end(&self, proc: &str, duration: Duration, outcome: CallOutcome) {
+        if proc == "slow" {
+            assert!(duration >= Duration::from_millis(50), "{:?}", duration);
+        }
+        self.record(format!("end {} {}", proc, outcome.as_str()));
+    }
+}
+
+#[tokio::test]
+async fn server_and_client_report_calls_and_connections() {
+    let server_metrics = Recorder::default();
+    let client_metrics = Recorder::default();
+
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    server.set_metrics(server_metrics.clone());
+    server
+        .add_proc("ok", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    server
+        .add_async_proc("slow", |_, _| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(ZRpcDt::Null)
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::builder(addr)
+        .metrics(client_metrics.clone())
+        .build()
+        .await
+        .unwrap();
+
+    client.call("ok", params!()).await.unwrap();
+    client.call("missing", params!()).await.unwrap_err();
+    client
+        .call_timeout("slow", params!(), Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    drop(client);
+
+    expect(
+        &client_metrics,
+        &[
+            "open",
+            "start ok",
+            "end ok success",
+            "start missing",
+            "end missing error",
+            "start slow",
+            "end slow timeout",
+            "close",
+        ],
+    )
+    .await;
+
+    // The call nobody waits for anymore is dropped with the connection.
+    expect(
+        &server_metrics,
+        &[
+            "open",
+            "start ok",
+            "end ok success",
+            "start missing",
+            "end missing error",
+            "start slow",
+            "end slow cancelled",
+            "close",
+        ],
+    )
+    .await;
+}
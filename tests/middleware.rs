@@ -0,0 +1,136 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    middleware::{Middleware, error::MiddlewareError},
+    middleware_err, params,
+    server::ZRpcServer,
+    types::{ctx::ZRpcContext, dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq},
+};
+
+struct Reject;
+
+impl Middleware for Reject {
+    fn before_call(&self, req: &mut ZRpcReq, _: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        match req.proc.as_str() {
+            "rejected" => middleware_err!("rejected"),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Count(Arc<AtomicUsize>);
+
+impl Middleware for Count {
+    fn before_call(&self, _: &mut ZRpcReq, _: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn rejecting_middleware_stops_the_call() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    let middleware = Arc::new(AtomicUsize::new(0));
+    let procedure = Arc::new(AtomicUsize::new(0));
+
+    server.add_middleware(Reject).await;
+    server.add_middleware(Count(middleware.clone())).await;
+
+    for name in ["rejected", "accepted"] {
+        let calls = procedure.clone();
+        server
+            .add_proc(name, move |_: &Vec<ZRpcDt>| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(ZRpcDt::Null)
+            })
+            .await;
+    }
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let res = client.call("rejected", params!()).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::Middleware(e))) if e == "rejected"
+        ),
+        "{:?}",
+        res
+    );
+    assert_eq!(middleware.load(Ordering::SeqCst), 0);
+    assert_eq!(procedure.load(Ordering::SeqCst), 0);
+
+    client.call("accepted", params!()).await.unwrap();
+    assert_eq!(middleware.load(Ordering::SeqCst), 1);
+    assert_eq!(procedure.load(Ordering::SeqCst), 1);
+}
+
+/// Fails every stream that ended well, and counts the streams it saw end.
+struct FailStreams(Arc<AtomicUsize>);
+
+impl Middleware for FailStreams {
+    fn after_call(
+        &self,
+        _: &ZRpcReq,
+        _: &ZRpcContext,
+        res: &mut Result<ZRpcDt, ProcedureError>,
+    ) -> Result<(), MiddlewareError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+
+        match res {
+            Ok(ZRpcDt::Null) => middleware_err!("stream refused"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn after_call_sees_the_end_of_streams() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    let ended = Arc::new(AtomicUsize::new(0));
+    server.add_middleware(FailStreams(ended.clone())).await;
+    server
+        .add_stream_proc("two", |_| {
+            Ok(futures::stream::iter([
+                Ok(ZRpcDt::Int32(1)),
+                Ok(ZRpcDt::Int32(2)),
+            ]))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let items: Vec<_> = client
+        .call_stream("two", params!())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    assert_eq!(items.len(), 3, "{:?}", items);
+    assert_eq!(items[0].as_ref().unwrap(), &ZRpcDt::Int32(1));
+    assert_eq!(items[1].as_ref().unwrap(), &ZRpcDt::Int32(2));
+    assert!(
+        matches!(
+            &items[2],
+            Err(ZRpcError::Procedure(ProcedureError::AfterMiddleware(e))) if e == "stream refused"
+        ),
+        "{:?}",
+        items[2]
+    );
+    assert_eq!(ended.load(Ordering::SeqCst), 1);
+}
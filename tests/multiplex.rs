@@ -0,0 +1,47 @@
+use std::{net::Ipv4Addr, sync::Mutex, time::Duration};
+
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test]
+async fn answers_arriving_out_of_order_reach_their_calls() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    // Answers with its parameter after as many milliseconds.
+    server
+        .add_async_proc("after", |_, params| async move {
+            match params.as_slice() {
+                [ZRpcDt::UInt64(ms)] => {
+                    tokio::time::sleep(Duration::from_millis(*ms)).await;
+                    Ok(ZRpcDt::UInt64(*ms))
+                }
+                _ => Err(ProcedureError::BadParams("expected a duration".to_string())),
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let finished = Mutex::new(Vec::new());
+    let call = |ms: u64| {
+        let (client, finished) = (&client, &finished);
+
+        async move {
+            let res = client.call("after", params!(ms)).await;
+            finished.lock().unwrap().push(ms);
+            res
+        }
+    };
+
+    // Sent in this order on the same connection, answered in reverse.
+    let (slow, medium, fast) = tokio::join!(call(300), call(150), call(0));
+    assert_eq!(slow.unwrap(), ZRpcDt::UInt64(300));
+    assert_eq!(medium.unwrap(), ZRpcDt::UInt64(150));
+    assert_eq!(fast.unwrap(), ZRpcDt::UInt64(0));
+    assert_eq!(*finished.lock().unwrap(), [0, 150, 300]);
+}
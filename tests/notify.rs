@@ -0,0 +1,37 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use libzrpc::{client::ZRpcClient, params, server::ZRpcServer, types::dt::ZRpcDt};
+
+#[tokio::test]
+async fn notification_outlives_the_connection() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    server
+        .add_async_proc("track", move |_, _| {
+            let flag = flag.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                flag.store(true, Ordering::SeqCst);
+                Ok(ZRpcDt::Null)
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+    client.notify("track", params!()).await.unwrap();
+    drop(client);
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(done.load(Ordering::SeqCst));
+}
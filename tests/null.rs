@@ -0,0 +1,76 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    transport::codec::ZRpcCodec,
+    types::dt::{FromZRpcDt, ZRpcDt},
+};
+
+async fn serve(codec: ZRpcCodec) -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(codec);
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| {
+            Ok(ZRpcDt::Array(params.clone()))
+        })
+        .await;
+    server
+        .add_typed_proc("find", |id: u32| (id == 1).then(|| "Ada".to_string()))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::builder(addr)
+        .codec(codec)
+        .build()
+        .await
+        .unwrap()
+}
+
+async fn null_stays_distinct_from_empty_values(codec: ZRpcCodec) {
+    let client = serve(codec).await;
+
+    let res = client
+        .call("echo", params!(None, "", 0i32, 0u64, false, Some(0i32)))
+        .await
+        .unwrap();
+    assert_eq!(
+        res,
+        ZRpcDt::Array(vec![
+            ZRpcDt::Null,
+            ZRpcDt::String(String::new()),
+            ZRpcDt::Int32(0),
+            ZRpcDt::UInt64(0),
+            ZRpcDt::Bool(false),
+            ZRpcDt::Int32(0),
+        ])
+    );
+
+    let found = client.call("find", params!(1u32)).await.unwrap();
+    assert_eq!(
+        Option::<String>::from_zdt(&found).unwrap(),
+        Some("Ada".to_string())
+    );
+    let missing = client.call("find", params!(2u32)).await.unwrap();
+    assert_eq!(missing, ZRpcDt::Null);
+    assert_eq!(Option::<String>::from_zdt(&missing).unwrap(), None);
+}
+
+#[tokio::test]
+async fn null_round_trips_with_bincode() {
+    null_stays_distinct_from_empty_values(ZRpcCodec::Bincode).await;
+}
+
+#[tokio::test]
+async fn null_round_trips_with_json() {
+    null_stays_distinct_from_empty_values(ZRpcCodec::Json).await;
+}
+
+#[cfg(feature = "msgpack")]
+#[tokio::test]
+async fn null_round_trips_with_msgpack() {
+    null_stays_distinct_from_empty_values(ZRpcCodec::MessagePack).await;
+}
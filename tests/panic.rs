@@ -0,0 +1,37 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test]
+async fn panicking_procedure_only_fails_its_call() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("panic", |_: &Vec<ZRpcDt>| panic!("boom"))
+        .await;
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| {
+            Ok(ZRpcDt::String("pong".to_string()))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let res = client.call("panic", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::Procedure(ProcedureError::Internal(_)))),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("ping", params!()).await.unwrap();
+    assert_eq!(res, ZRpcDt::String("pong".to_string()));
+}
@@ -0,0 +1,157 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    middleware::{Middleware, error::MiddlewareError},
+    params,
+    server::ZRpcServer,
+    types::{ctx::ZRpcContext, dt::ZRpcDt, proc_error::ProcedureError, req::ZRpcReq},
+};
+
+/// Only lets calls from loopback addresses reach `admin`.
+struct Allowlist;
+
+impl Middleware for Allowlist {
+    fn before_call(&self, req: &mut ZRpcReq, ctx: &mut ZRpcContext) -> Result<(), MiddlewareError> {
+        match ctx.peer_addr() {
+            _ if req.proc != "admin" => Ok(()),
+            Some(addr) if addr.ip().is_loopback() => Ok(()),
+            _ => Err(MiddlewareError::Unauthorized),
+        }
+    }
+}
+
+async fn register(server: &mut ZRpcServer) {
+    server.add_middleware(Allowlist).await;
+    server
+        .add_proc_with_ctx("whoami", |ctx, _| {
+            let addr = ctx
+                .peer_addr()
+                .map_or(ZRpcDt::Null, |addr| ZRpcDt::String(addr.to_string()));
+            let identity = ctx
+                .peer_identity()
+                .map_or(ZRpcDt::Null, |id| ZRpcDt::String(id.subject().to_string()));
+
+            Ok(ZRpcDt::Array(vec![addr, identity]))
+        })
+        .await;
+    server
+        .add_typed_proc("admin", || "welcome".to_string())
+        .await;
+}
+
+fn whoami(res: ZRpcDt) -> (Option<SocketAddr>, Option<String>) {
+    let ZRpcDt::Array(values) = res else {
+        panic!("expected an array, got {:?}", res);
+    };
+
+    match &values[..] {
+        [addr, identity] => (
+            match addr {
+                ZRpcDt::String(addr) => Some(addr.parse().unwrap()),
+                _ => None,
+            },
+            match identity {
+                ZRpcDt::String(subject) => Some(subject.clone()),
+                _ => None,
+            },
+        ),
+        _ => panic!("expected two values, got {:?}", values),
+    }
+}
+
+#[tokio::test]
+async fn tcp_calls_see_the_client_address_but_no_identity() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    register(&mut server).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let (peer, identity) = whoami(client.call("whoami", params!()).await.unwrap());
+    let peer = peer.unwrap();
+    assert!(peer.ip().is_loopback());
+    assert_ne!(peer.port(), addr.port());
+    assert_eq!(identity, None);
+
+    assert_eq!(
+        client.call("admin", params!()).await.unwrap(),
+        ZRpcDt::String("welcome".to_string())
+    );
+}
+
+#[tokio::test]
+async fn in_memory_calls_have_no_address() {
+    let mut server = ZRpcServer::new_in_memory();
+    register(&mut server).await;
+    let handle = server.handle();
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new_in_memory(&handle).await.unwrap();
+
+    assert_eq!(
+        whoami(client.call("whoami", params!()).await.unwrap()),
+        (None, None)
+    );
+
+    let res = client.call("admin", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::Procedure(ProcedureError::Unauthorized))),
+        "{:?}",
+        res
+    );
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn mutual_tls_calls_see_the_client_certificate() {
+    use libzrpc::transport::tls::{
+        self,
+        rustls::{
+            RootCertStore,
+            pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        },
+    };
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca = CertificateParams::new(Vec::new()).unwrap();
+    ca.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca.distinguished_name.push(DnType::CommonName, "test ca");
+    let ca = ca.self_signed(&ca_key).unwrap();
+    let mut roots = RootCertStore::empty();
+    roots.add(ca.der().clone()).unwrap();
+
+    // Signs a certificate for `name`, returning it with its key.
+    let issue = |name: &str, alt_names: Vec<String>| {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(alt_names).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+        (
+            vec![cert.der().clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+        )
+    };
+
+    let (chain, key) = issue("server", vec!["localhost".to_string()]);
+    let config = tls::server_config(chain, key, Some(roots.clone())).unwrap();
+    let mut server = ZRpcServer::new_tls((Ipv4Addr::LOCALHOST, 0), config)
+        .await
+        .unwrap();
+    let addr = server.local_addrs()[0];
+    register(&mut server).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::builder(("localhost", addr.port()))
+        .tls(tls::client_config(roots, Some(issue("alice", Vec::new()))).unwrap())
+        .build()
+        .await
+        .unwrap();
+
+    let (peer, identity) = whoami(client.call("whoami", params!()).await.unwrap());
+    assert!(peer.unwrap().ip().is_loopback());
+    assert_eq!(identity.as_deref(), Some("CN=alice"));
+}
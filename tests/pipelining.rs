@@ -0,0 +1,135 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use libzrpc::{
+    client::ZRpcClient, error::ZRpcError, params, server::ZRpcServer, types::dt::ZRpcDt,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// `delay` sleeps the given milliseconds and answers with them. The returned
+/// counter holds the most calls that ran at once.
+async fn serve() -> (SocketAddr, Arc<AtomicUsize>) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let running = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+
+    server
+        .add_async_proc("delay", {
+            let most = most.clone();
+
+            move |_, params| {
+                let running = running.clone();
+                let most = most.clone();
+
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+
+                    let ms = match params[..] {
+                        [ZRpcDt::UInt64(ms)] => ms,
+                        _ => 0,
+                    };
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(ZRpcDt::UInt64(ms))
+                }
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    (addr, most)
+}
+
+/// Calls `delay` with each of `delays`, one after the other without waiting
+/// for the answers, reporting the answers in the order they are returned.
+async fn pipeline(
+    client: &ZRpcClient,
+    delays: &[u64],
+) -> (Vec<JoinHandle<()>>, mpsc::UnboundedReceiver<u64>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut calls = Vec::new();
+
+    for &ms in delays {
+        let client = client.clone();
+        let tx = tx.clone();
+
+        calls.push(tokio::spawn(async move {
+            if let Ok(ZRpcDt::UInt64(ms)) = client.call("delay", params!(ms)).await {
+                let _ = tx.send(ms);
+            }
+        }));
+        // Lets the call send its request before the next one.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    (calls, rx)
+}
+
+async fn answers(mut rx: mpsc::UnboundedReceiver<u64>, count: usize) -> Vec<u64> {
+    let mut answers = Vec::new();
+    while answers.len() < count {
+        answers.push(rx.recv().await.unwrap());
+    }
+    answers
+}
+
+#[tokio::test]
+async fn max_inflight_caps_the_calls_awaiting_answers() {
+    let (addr, most) = serve().await;
+    let client = ZRpcClient::builder(addr)
+        .max_inflight(2)
+        .build()
+        .await
+        .unwrap();
+
+    let (_calls, rx) = pipeline(&client, &[100; 6]).await;
+    assert_eq!(answers(rx, 6).await, vec![100; 6]);
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+
+    // Calls waiting for room are still bounded by their timeout.
+    let (_calls, _rx) = pipeline(&client, &[500, 500]).await;
+    let res = client
+        .call_timeout("delay", params!(0u64), Duration::from_millis(100))
+        .await;
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+}
+
+#[tokio::test]
+async fn answers_arrive_as_they_finish_by_default() {
+    let (addr, most) = serve().await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let (_calls, rx) = pipeline(&client, &[300, 50, 150]).await;
+    assert_eq!(answers(rx, 3).await, vec![50, 150, 300]);
+    assert_eq!(most.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn ordered_clients_answer_in_request_order() {
+    let (addr, most) = serve().await;
+    let client = ZRpcClient::builder(addr)
+        .ordered(true)
+        .build()
+        .await
+        .unwrap();
+
+    let (_calls, rx) = pipeline(&client, &[300, 50, 150]).await;
+    assert_eq!(answers(rx, 3).await, vec![300, 50, 150]);
+    // The server still ran them concurrently.
+    assert_eq!(most.load(Ordering::SeqCst), 3);
+
+    // A dropped call doesn't hold back the ones after it.
+    let (calls, rx) = pipeline(&client, &[5000, 50]).await;
+    calls[0].abort();
+    let answered = tokio::time::timeout(Duration::from_secs(1), answers(rx, 1)).await;
+    assert_eq!(answered.unwrap(), vec![50]);
+}
@@ -0,0 +1,148 @@
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+
+use libzrpc::{
+    client::pool::{ZRpcClientPool, ZRpcPoolStrategy},
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+use tokio::time::timeout;
+
+async fn serve() -> std::net::SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_async_proc("sleep", |_, params| async move {
+            match params.as_slice() {
+                [ZRpcDt::UInt64(ms)] => {
+                    tokio::time::sleep(Duration::from_millis(*ms)).await;
+                    Ok(ZRpcDt::UInt64(*ms))
+                }
+                _ => Err(ProcedureError::BadParams("expected a duration".to_string())),
+            }
+        })
+        .await;
+    // Tell calls which connection they came over.
+    server
+        .add_proc_with_ctx("port", |ctx, _| {
+            Ok(ZRpcDt::UInt16(ctx.peer_addr().unwrap().port()))
+        })
+        .await;
+    server
+        .add_async_proc("slow_port", |ctx, _| async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(ZRpcDt::UInt16(ctx.peer_addr().unwrap().port()))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn queued_calls_never_exhaust_the_pool() {
+    let addr = serve().await;
+
+    for strategy in [ZRpcPoolStrategy::RoundRobin, ZRpcPoolStrategy::LeastBusy] {
+        let pool = Arc::new(
+            ZRpcClientPool::builder(addr)
+                .max_size(2)
+                .strategy(strategy)
+                .build()
+                .await
+                .unwrap(),
+        );
+
+        let tasks: Vec<_> = (0..64u64)
+            .map(|n| {
+                let pool = pool.clone();
+                tokio::spawn(async move { pool.call("sleep", params!(n % 3)).await })
+            })
+            .collect();
+
+        for (n, task) in tasks.into_iter().enumerate() {
+            assert_eq!(task.await.unwrap().unwrap(), ZRpcDt::UInt64(n as u64 % 3));
+        }
+    }
+}
+
+#[tokio::test]
+async fn least_busy_avoids_the_connection_of_a_slow_call() {
+    let addr = serve().await;
+
+    for (strategy, shares_slow_connection) in [
+        (ZRpcPoolStrategy::RoundRobin, true),
+        (ZRpcPoolStrategy::LeastBusy, false),
+    ] {
+        let pool = Arc::new(
+            ZRpcClientPool::builder(addr)
+                .max_size(2)
+                .max_calls_per_connection(4)
+                .strategy(strategy)
+                .build()
+                .await
+                .unwrap(),
+        );
+
+        let slow = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.call("slow_port", params!()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let first = pool.call("port", params!()).await.unwrap();
+        let second = pool.call("port", params!()).await.unwrap();
+        let slow = slow.await.unwrap().unwrap();
+
+        assert_ne!(first, slow, "{:?}", strategy);
+        assert_eq!(second == slow, shares_slow_connection, "{:?}", strategy);
+    }
+}
+
+#[tokio::test]
+async fn busy_pool_without_queue_is_exhausted() {
+    let addr = serve().await;
+    let pool = Arc::new(
+        ZRpcClientPool::builder(addr)
+            .max_size(1)
+            .queue(false)
+            .build()
+            .await
+            .unwrap(),
+    );
+
+    let busy = {
+        let pool = pool.clone();
+        tokio::spawn(async move { pool.call("sleep", params!(200u64)).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let res = pool.call("sleep", params!(0u64)).await;
+    assert!(matches!(res, Err(ZRpcError::PoolExhausted)), "{:?}", res);
+    busy.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn dropped_call_returns_its_connection() {
+    let addr = serve().await;
+    let pool = ZRpcClientPool::builder(addr)
+        .max_size(1)
+        .queue(false)
+        .build()
+        .await
+        .unwrap();
+    let port = pool.call("port", params!()).await.unwrap();
+
+    let dropped = timeout(
+        Duration::from_millis(50),
+        pool.call("sleep", params!(1000u64)),
+    )
+    .await;
+    assert!(dropped.is_err());
+
+    let res = pool.call("sleep", params!(0u64)).await.unwrap();
+    assert_eq!(res, ZRpcDt::UInt64(0));
+    assert_eq!(pool.call("port", params!()).await.unwrap(), port);
+}
@@ -0,0 +1,111 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    middleware::rate_limit::RateLimitMiddleware,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, req::ZRpcHeaders},
+};
+
+/// Serves `ping` behind `limit`, counting the calls that get through.
+async fn serve(limit: RateLimitMiddleware) -> (SocketAddr, Arc<AtomicUsize>) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    server.add_middleware(limit).await;
+    server
+        .add_proc("ping", {
+            let calls = calls.clone();
+
+            move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(ZRpcDt::Null)
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    (addr, calls)
+}
+
+fn tenant(name: &str) -> ZRpcHeaders {
+    ZRpcHeaders::from([("tenant".to_string(), name.to_string())])
+}
+
+#[tokio::test]
+async fn calls_over_the_burst_are_rejected_before_the_handler() {
+    let (addr, calls) = serve(RateLimitMiddleware::new(1.0, 2)).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    for _ in 0..2 {
+        client.call("ping", params!()).await.unwrap();
+    }
+
+    let res = client.call("ping", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::RateLimited(after)) if after > Duration::from_millis(500) && after <= Duration::from_secs(1)),
+        "{:?}",
+        res
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // Other clients from the same address share the bucket.
+    let other = ZRpcClient::new(addr).await.unwrap();
+    let res = other.call("ping", params!()).await;
+    assert!(matches!(res, Err(ZRpcError::RateLimited(_))), "{:?}", res);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn buckets_refill_at_the_rate() {
+    let (addr, calls) = serve(RateLimitMiddleware::new(20.0, 1)).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    client.call("ping", params!()).await.unwrap();
+    let res = client.call("ping", params!()).await;
+    let Err(ZRpcError::RateLimited(after)) = res else {
+        panic!("{:?}", res);
+    };
+    assert!(after <= Duration::from_millis(50), "{:?}", after);
+
+    tokio::time::sleep(after).await;
+    client.call("ping", params!()).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn calls_can_be_limited_by_tenant() {
+    let limit =
+        RateLimitMiddleware::new(0.1, 1).key_by(|req, _| req.headers.get("tenant").cloned());
+    let (addr, calls) = serve(limit).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    client
+        .call_with_headers("ping", params!(), tenant("acme"))
+        .await
+        .unwrap();
+    let res = client
+        .call_with_headers("ping", params!(), tenant("acme"))
+        .await;
+    assert!(matches!(res, Err(ZRpcError::RateLimited(_))), "{:?}", res);
+
+    // Each tenant has its own bucket, and calls without one aren't limited.
+    client
+        .call_with_headers("ping", params!(), tenant("globex"))
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        client.call("ping", params!()).await.unwrap();
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+}
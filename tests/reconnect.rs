@@ -0,0 +1,183 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use libzrpc::{
+    client::{ConnectionState, ZRpcClient},
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::dt::ZRpcDt,
+};
+use tokio::{sync::oneshot, task::JoinHandle};
+
+/// A server counting the calls of `work`, which answer after `delay`. It
+/// drops its connections at once when told to stop.
+struct Server {
+    addr: SocketAddr,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    async fn start(port: u16, calls: Arc<AtomicUsize>, delay: Duration) -> Self {
+        let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, port)).await.unwrap();
+        let addr = server.local_addrs()[0];
+
+        server.set_grace_period(Duration::ZERO);
+        server
+            .add_async_proc("work", move |_, _| {
+                let calls = calls.clone();
+
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    Ok(ZRpcDt::Null)
+                }
+            })
+            .await;
+
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            server
+                .start_with_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await
+                .unwrap();
+        });
+
+        Self { addr, stop, task }
+    }
+
+    /// Returns once the server no longer listens.
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        self.task.await.unwrap();
+    }
+}
+
+async fn client(addr: SocketAddr) -> ZRpcClient {
+    ZRpcClient::builder(addr)
+        .reconnect(true)
+        .backoff(Duration::from_millis(20))
+        .max_backoff(Duration::from_millis(20))
+        .max_retries(100)
+        .timeout(Duration::from_secs(10))
+        .build()
+        .await
+        .unwrap()
+}
+
+async fn wait_for(calls: &AtomicUsize, n: usize) {
+    while calls.load(Ordering::SeqCst) < n {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn reconnects_after_the_server_restarts() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let first = Server::start(0, calls.clone(), Duration::ZERO).await;
+    let port = first.addr.port();
+    let client = client(first.addr).await;
+    client.call("work", params!()).await.unwrap();
+
+    let mut states = client.state_changes();
+    first.stop().await;
+    states
+        .wait_for(|state| *state == ConnectionState::Disconnected)
+        .await
+        .unwrap();
+
+    let second = Server::start(port, calls.clone(), Duration::ZERO).await;
+    client.call("work", params!()).await.unwrap();
+    assert!(client.is_connected());
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    second.stop().await;
+}
+
+#[tokio::test]
+async fn plain_call_is_not_resent_after_a_reconnect() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let first = Server::start(0, calls.clone(), Duration::from_secs(10)).await;
+    let port = first.addr.port();
+    let client = client(first.addr).await;
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("work", params!()).await })
+    };
+    wait_for(&calls, 1).await;
+    first.stop().await;
+
+    let res = call.await.unwrap();
+    assert!(matches!(res, Err(ZRpcError::Io(_))), "{:?}", res);
+
+    let second = Server::start(port, calls.clone(), Duration::ZERO).await;
+    client.call("work", params!()).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    second.stop().await;
+}
+
+#[tokio::test]
+async fn idempotent_call_is_resent_exactly_once() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let first = Server::start(0, calls.clone(), Duration::from_secs(10)).await;
+    let port = first.addr.port();
+    let client = client(first.addr).await;
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call_idempotent("work", params!()).await })
+    };
+    wait_for(&calls, 1).await;
+    first.stop().await;
+
+    // The re-sent call is dropped midway as well, and not sent a third time.
+    let second = Server::start(port, calls.clone(), Duration::from_secs(10)).await;
+    wait_for(&calls, 2).await;
+    second.stop().await;
+
+    let res = call.await.unwrap();
+    assert!(matches!(res, Err(ZRpcError::Io(_))), "{:?}", res);
+
+    let third = Server::start(port, calls.clone(), Duration::ZERO).await;
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call_idempotent("work", params!()).await })
+    };
+    let res = call.await.unwrap();
+    assert_eq!(res.unwrap(), ZRpcDt::Null);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    third.stop().await;
+}
+
+#[tokio::test]
+async fn idempotent_call_survives_a_restart() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let first = Server::start(0, calls.clone(), Duration::from_secs(10)).await;
+    let port = first.addr.port();
+    let client = client(first.addr).await;
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call_idempotent("work", params!()).await })
+    };
+    wait_for(&calls, 1).await;
+    first.stop().await;
+
+    let second = Server::start(port, calls.clone(), Duration::ZERO).await;
+    assert_eq!(call.await.unwrap().unwrap(), ZRpcDt::Null);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    second.stop().await;
+}
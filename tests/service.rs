@@ -0,0 +1,56 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::{ZRpcServer, service::ZRpcService},
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    let mut users = ZRpcService::new();
+    users.add_typed_proc("add", |name: String| format!("user {}", name));
+    let mut billing = ZRpcService::new();
+    billing.add_typed_proc("add", |cents: u64| cents * 2);
+
+    server.add_service("users", users).await;
+    server.add_service("billing", billing).await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn services_with_the_same_names_stay_apart() {
+    let client = serve().await;
+
+    assert_eq!(
+        client.call("users.add", params!("Ada")).await.unwrap(),
+        ZRpcDt::String("user Ada".to_string())
+    );
+    assert_eq!(
+        client.call("billing.add", params!(21u64)).await.unwrap(),
+        ZRpcDt::UInt64(42)
+    );
+}
+
+#[tokio::test]
+async fn unknown_names_fail_with_the_full_name() {
+    let client = serve().await;
+
+    for name in ["nope.add", "users.remove", "add"] {
+        let res = client.call(name, params!()).await;
+        assert!(
+            matches!(
+                &res,
+                Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(called))) if called == name
+            ),
+            "{:?}",
+            res
+        );
+    }
+}
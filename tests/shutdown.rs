@@ -0,0 +1,85 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+use tokio::{sync::oneshot, task::JoinHandle};
+
+/// A server whose `slow` procedure answers after 300ms, shutting down once
+/// told to.
+async fn serve(grace_period: Duration) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<()>) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_grace_period(grace_period);
+    server
+        .add_async_proc("slow", |_, _| async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(ZRpcDt::Null)
+        })
+        .await;
+
+    let (stop, stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        server
+            .start_with_shutdown(async {
+                let _ = stopped.await;
+            })
+            .await
+            .unwrap();
+    });
+
+    (addr, stop, task)
+}
+
+#[tokio::test]
+async fn running_calls_finish_during_the_grace_period() {
+    let (addr, stop, task) = serve(Duration::from_secs(5)).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("slow", params!()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stop.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // New calls are turned away while the running one finishes.
+    let res = client.call("slow", params!()).await;
+    assert!(
+        matches!(res, Err(ZRpcError::Procedure(ProcedureError::ShuttingDown))),
+        "{:?}",
+        res
+    );
+    assert_eq!(call.await.unwrap().unwrap(), ZRpcDt::Null);
+
+    tokio::time::timeout(Duration::from_secs(5), task)
+        .await
+        .expect("the server kept running")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn calls_outlasting_the_grace_period_are_dropped() {
+    let (addr, stop, task) = serve(Duration::from_millis(50)).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("slow", params!()).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stop.send(()).unwrap();
+
+    let res = call.await.unwrap();
+    assert!(matches!(res, Err(ZRpcError::Io(_))), "{:?}", res);
+    task.await.unwrap();
+}
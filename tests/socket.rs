@@ -0,0 +1,73 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+use libzrpc::{
+    client::ZRpcClient, error::ZRpcError, params, server::ZRpcServer, types::dt::ZRpcDt,
+};
+use socket2::{Domain, Socket, Type};
+
+async fn serve() -> SocketAddr {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    addr
+}
+
+#[tokio::test]
+async fn tuned_sockets_carry_small_and_large_calls() {
+    let client = ZRpcClient::builder(serve().await)
+        .nodelay(true)
+        .send_buffer_size(4096)
+        .recv_buffer_size(4096)
+        .connect_timeout(Duration::from_secs(5))
+        .build()
+        .await
+        .unwrap();
+
+    for i in 0..100 {
+        assert_eq!(
+            client.call("echo", params!(i)).await.unwrap(),
+            ZRpcDt::Int32(i)
+        );
+    }
+
+    let big = ZRpcDt::Bytes(vec![7; 1 << 20]);
+    assert_eq!(client.call("echo", vec![big.clone()]).await.unwrap(), big);
+}
+
+#[tokio::test]
+async fn connecting_gives_up_after_the_connect_timeout() {
+    // A listener that never accepts, whose backlog fills with one connection,
+    // so the next one is never established.
+    let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    listener
+        .bind(&SocketAddr::from((Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+    listener.listen(0).unwrap();
+    let addr = listener.local_addr().unwrap().as_socket().unwrap();
+    let _queued = TcpStream::connect(addr).unwrap();
+
+    let started = Instant::now();
+    let res = ZRpcClient::builder(addr)
+        .connect_timeout(Duration::from_millis(200))
+        .build()
+        .await;
+
+    assert!(
+        matches!(res, Err(ZRpcError::TimeoutError)),
+        "{:?}",
+        res.map(|_| ())
+    );
+    assert!(
+        started.elapsed() < Duration::from_secs(2),
+        "{:?}",
+        started.elapsed()
+    );
+}
@@ -0,0 +1,90 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use libzrpc::{
+    client::{ConnectionState, ZRpcClient},
+    params,
+    server::ZRpcServer,
+    types::dt::ZRpcDt,
+};
+use tokio::{sync::oneshot, task::JoinHandle, time::timeout};
+
+/// Starts a server on `port`, returning a sender that stops it and the
+/// task that ends once it has.
+async fn serve(port: u16) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<()>) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, port)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_grace_period(Duration::ZERO);
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+
+    let (stop, stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        server
+            .start_with_shutdown(async {
+                let _ = stopped.await;
+            })
+            .await
+            .unwrap();
+    });
+
+    (addr, stop, task)
+}
+
+async fn wait_for(client: &ZRpcClient, state: ConnectionState) {
+    let mut states = client.state_changes();
+
+    timeout(Duration::from_secs(5), states.wait_for(|s| *s == state))
+        .await
+        .unwrap_or_else(|_| panic!("never became {:?}", state))
+        .unwrap();
+}
+
+#[tokio::test]
+async fn state_follows_the_connection_without_calls() {
+    let (addr, stop, task) = serve(0).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+    assert_eq!(client.connection_state(), ConnectionState::Connected);
+    assert!(client.is_connected());
+
+    stop.send(()).unwrap();
+    task.await.unwrap();
+    wait_for(&client, ConnectionState::Disconnected).await;
+    assert!(!client.is_connected());
+}
+
+#[tokio::test]
+async fn reconnecting_client_reports_each_transition() {
+    let (addr, stop, task) = serve(0).await;
+    let client = ZRpcClient::builder(addr)
+        .reconnect(true)
+        .backoff(Duration::from_millis(20))
+        .max_backoff(Duration::from_millis(20))
+        .max_retries(100)
+        .build()
+        .await
+        .unwrap();
+
+    stop.send(()).unwrap();
+    task.await.unwrap();
+    wait_for(&client, ConnectionState::Disconnected).await;
+
+    // The call re-dials until the server is back.
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call("ping", params!()).await })
+    };
+    wait_for(&client, ConnectionState::Connecting).await;
+    let (_, stop, task) = serve(addr.port()).await;
+    wait_for(&client, ConnectionState::Connected).await;
+
+    assert_eq!(call.await.unwrap().unwrap(), ZRpcDt::Null);
+    assert!(client.is_connected());
+
+    stop.send(()).unwrap();
+    task.await.unwrap();
+}
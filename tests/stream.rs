@@ -0,0 +1,171 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient, params, server::ZRpcServer, transport::codec::ZRpcCodec, types::dt::ZRpcDt,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// Sets its flag when dropped, i.e. when the server stops the stream.
+struct Dropped(Arc<AtomicBool>);
+
+impl Drop for Dropped {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+struct Counters {
+    produced: Arc<AtomicUsize>,
+    dropped: Arc<AtomicBool>,
+    answered: Arc<AtomicUsize>,
+}
+
+async fn serve(codec: ZRpcCodec) -> (SocketAddr, Counters) {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+    let counters = Counters {
+        produced: Arc::new(AtomicUsize::new(0)),
+        dropped: Arc::new(AtomicBool::new(false)),
+        answered: Arc::new(AtomicUsize::new(0)),
+    };
+
+    server.set_codec(codec);
+    // Counts as long as it is polled.
+    let (produced, dropped) = (counters.produced.clone(), counters.dropped.clone());
+    server
+        .add_stream_proc("count", move |_| {
+            let produced = produced.clone();
+            let guard = Dropped(dropped.clone());
+
+            Ok(futures::stream::iter(0u64..).map(move |n| {
+                let _ = &guard;
+                produced.fetch_add(1, Ordering::SeqCst);
+                Ok(ZRpcDt::UInt64(n))
+            }))
+        })
+        .await;
+    let answered = counters.answered.clone();
+    server
+        .add_proc("big", move |_: &Vec<ZRpcDt>| {
+            answered.fetch_add(1, Ordering::SeqCst);
+            Ok(ZRpcDt::Bytes(vec![7; 32 * 1024]))
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    (addr, counters)
+}
+
+#[tokio::test]
+async fn server_stays_within_the_credit_of_a_slow_consumer() {
+    let (addr, counters) = serve(ZRpcCodec::Bincode).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let mut stream = client.call_stream("count", params!()).await.unwrap();
+
+    // Nothing is read, so the server stops after the first window.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let ahead = counters.produced.load(Ordering::SeqCst);
+    assert!(ahead > 0 && ahead <= 16, "{} chunks produced", ahead);
+
+    for n in 0..40u64 {
+        assert_eq!(stream.next().await.unwrap().unwrap(), ZRpcDt::UInt64(n));
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let ahead = counters.produced.load(Ordering::SeqCst);
+    assert!(ahead <= 40 + 16, "{} chunks produced", ahead);
+}
+
+#[tokio::test]
+async fn dropping_the_stream_stops_the_server() {
+    let (addr, counters) = serve(ZRpcCodec::Bincode).await;
+    let client = ZRpcClient::new(addr).await.unwrap();
+    let mut stream = client.call_stream("count", params!()).await.unwrap();
+
+    assert_eq!(stream.next().await.unwrap().unwrap(), ZRpcDt::UInt64(0));
+    drop(stream);
+
+    timeout(Duration::from_secs(5), async {
+        while !counters.dropped.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("the server kept the stream open");
+    let produced = counters.produced.load(Ordering::SeqCst);
+
+    // The connection is still usable.
+    let mut stream = client.call_stream("count", params!()).await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), ZRpcDt::UInt64(0));
+    assert!(counters.produced.load(Ordering::SeqCst) > produced);
+}
+
+async fn send(stream: &mut TcpStream, frame: &[u8]) {
+    stream
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(frame).await.unwrap();
+}
+
+async fn receive(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).await.unwrap();
+    frame
+}
+
+#[tokio::test]
+async fn server_stops_reading_calls_of_a_client_that_does_not_read() {
+    const CALLS: usize = 1000;
+
+    let (addr, counters) = serve(ZRpcCodec::Json).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    send(&mut stream, b"\xFF{\"protocol\":1,\"codecs\":[2]}").await;
+    receive(&mut stream).await;
+
+    // Written by hand, as a client would read its answers.
+    let calls: Vec<u8> = (0..CALLS)
+        .flat_map(|id| {
+            let call = format!(
+                "\x02{{\"Call\":[{},{{\"proc\":\"big\",\"params\":[],\"auth_key\":null,\"timeout\":null,\"headers\":{{}}}}]}}",
+                id
+            );
+            let mut frame = (call.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(call.as_bytes());
+            frame
+        })
+        .collect();
+    let (mut reader, mut writer) = stream.into_split();
+    // Kept open, as the server drops clients that close their side.
+    let writing = tokio::spawn(async move {
+        writer.write_all(&calls).await.unwrap();
+        writer
+    });
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let answered = counters.answered.load(Ordering::SeqCst);
+    assert!(answered < CALLS / 2, "{} calls answered", answered);
+
+    // Once the client reads, all of its calls are answered.
+    let mut len = [0u8; 4];
+    for _ in 0..CALLS {
+        reader.read_exact(&mut len).await.unwrap();
+        let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut frame).await.unwrap();
+    }
+    assert_eq!(counters.answered.load(Ordering::SeqCst), CALLS);
+    drop(writing.await.unwrap());
+}
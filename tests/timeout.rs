@@ -0,0 +1,78 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    // Answers with its second parameter after the first, in milliseconds.
+    server
+        .add_async_proc("echo_after", |_, params| async move {
+            match params.as_slice() {
+                [ZRpcDt::UInt64(ms), n] => {
+                    tokio::time::sleep(Duration::from_millis(*ms)).await;
+                    Ok(n.clone())
+                }
+                _ => Err(ProcedureError::BadParams("expected a duration".to_string())),
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn slow_call_times_out_and_the_connection_stays_usable() {
+    let client = serve().await;
+
+    let res = client
+        .call_timeout(
+            "echo_after",
+            params!(500u64, 1u64),
+            Duration::from_millis(50),
+        )
+        .await;
+    assert!(matches!(res, Err(ZRpcError::TimeoutError)), "{:?}", res);
+
+    let res = client.call("echo_after", params!(0u64, 2u64)).await;
+    assert_eq!(res.unwrap(), ZRpcDt::UInt64(2));
+}
+
+#[tokio::test]
+async fn replies_racing_the_timeout_never_reach_another_call() {
+    let client = serve().await;
+    let mut answered = 0;
+
+    // The answers arrive right around the timeout, so some of them come in
+    // just after their call gave up.
+    for n in 0..60u64 {
+        let res = client
+            .call_timeout(
+                "echo_after",
+                params!(15 + n % 10, n),
+                Duration::from_millis(20),
+            )
+            .await;
+
+        match res {
+            Ok(dt) => {
+                assert_eq!(dt, ZRpcDt::UInt64(n));
+                answered += 1;
+            }
+            Err(ZRpcError::TimeoutError) => {}
+            Err(e) => panic!("call {} failed: {}", n, e),
+        }
+    }
+
+    assert!(answered > 0);
+    let res = client.call("echo_after", params!(0u64, 100u64)).await;
+    assert_eq!(res.unwrap(), ZRpcDt::UInt64(100));
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    transport::tls::rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+};
+
+fn config() -> Arc<ClientConfig> {
+    Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth(),
+    )
+}
+
+#[tokio::test]
+async fn invalid_host_name_fails_the_build() {
+    let res = ZRpcClient::builder(("not a host", 3000))
+        .tls(config())
+        .build()
+        .await;
+
+    assert!(matches!(res, Err(ZRpcError::Tls(_))), "{:?}", res.err());
+}
+
+#[tokio::test]
+async fn server_name_set_before_tls_is_kept() {
+    let name = ServerName::try_from("example.com").unwrap();
+
+    // The name replaces the invalid host name, so the client gets as far as
+    // resolving the host.
+    let res = ZRpcClient::builder(("not a host", 3000))
+        .tls_server_name(name)
+        .tls(config())
+        .build()
+        .await;
+
+    assert!(matches!(res, Err(ZRpcError::Io(_))), "{:?}", res.err());
+}
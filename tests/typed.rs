@@ -0,0 +1,48 @@
+use std::net::Ipv4Addr;
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+#[tokio::test]
+async fn mismatched_parameters_say_what_is_wrong() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_typed_proc::<(i32, i32), i32>("add", |a, b| a + b)
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::new(addr).await.unwrap();
+
+    assert_eq!(
+        client.call("add", params!(2, 3)).await.unwrap(),
+        ZRpcDt::Int32(5)
+    );
+
+    let res = client.call("add", params!(2)).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(e)))
+                if e == "expected 2 parameters, found 1"
+        ),
+        "{:?}",
+        res
+    );
+
+    let res = client.call("add", params!(2, "three")).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(e))) if e.starts_with("parameter 1: ")
+        ),
+        "{:?}",
+        res
+    );
+}
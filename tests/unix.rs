@@ -0,0 +1,28 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+
+use libzrpc::{client::ZRpcClient, params, server::ZRpcServer, types::dt::ZRpcDt};
+
+#[tokio::test]
+async fn socket_is_created_with_its_mode() {
+    let dir = std::env::temp_dir().join(format!("libzrpc-unix-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.sock");
+
+    let mut server = ZRpcServer::new_unix_with_mode(&path, 0o600).await.unwrap();
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(entries.len(), 1, "the private directory is left behind");
+
+    tokio::spawn(async move { server.start().await });
+    let client = ZRpcClient::new_unix(&path).await.unwrap();
+    assert!(client.call("ping", params!()).await.is_ok());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,190 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::ZRpcServer,
+    types::{dt::ZRpcDt, proc_error::ProcedureError},
+};
+use tokio::{
+    io::AsyncReadExt,
+    sync::{Semaphore, mpsc},
+    time::timeout,
+};
+
+/// `size` answers with the length and a checksum of what it read. `slow`
+/// only reads a chunk for every permit added to the semaphore. `peek` reads
+/// one chunk and answers right away, and `hang` waits for the upload to
+/// fail, reporting the error.
+async fn serve(
+    permits: Arc<Semaphore>,
+    errors: mpsc::UnboundedSender<std::io::ErrorKind>,
+) -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_upload_proc("size", |_, _, mut upload| async move {
+            let mut data = Vec::new();
+            upload
+                .read_to_end(&mut data)
+                .await
+                .map_err(|e| ProcedureError::Internal(e.to_string()))?;
+
+            let sum = data.iter().map(|b| *b as u64).sum::<u64>();
+            Ok(ZRpcDt::Array(vec![
+                ZRpcDt::UInt64(data.len() as u64),
+                ZRpcDt::UInt64(sum),
+            ]))
+        })
+        .await;
+    server
+        .add_upload_proc("slow", move |_, _, mut upload| {
+            let permits = permits.clone();
+
+            async move {
+                let mut chunks = 0u64;
+                loop {
+                    permits.acquire().await.unwrap().forget();
+                    match upload.chunk().await {
+                        Ok(Some(_)) => chunks += 1,
+                        Ok(None) => return Ok(ZRpcDt::UInt64(chunks)),
+                        Err(e) => return Err(ProcedureError::Internal(e.to_string())),
+                    }
+                }
+            }
+        })
+        .await;
+    server
+        .add_upload_proc("peek", |_, _, mut upload| async move {
+            let first = upload.chunk().await.unwrap().unwrap_or_default();
+            Ok(ZRpcDt::Bytes(first))
+        })
+        .await;
+    server
+        .add_upload_proc("hang", move |_, _, mut upload| {
+            let errors = errors.clone();
+
+            async move {
+                let mut data = Vec::new();
+                if let Err(e) = upload.read_to_end(&mut data).await {
+                    let _ = errors.send(e.kind());
+                }
+                Ok(ZRpcDt::Null)
+            }
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn large_uploads_arrive_whole() {
+    let (tx, _) = mpsc::unbounded_channel();
+    let client = serve(Arc::new(Semaphore::new(0)), tx).await;
+    let chunks = (0..64u8).map(|i| vec![i; 64 * 1024]);
+
+    let res = client
+        .call_upload("size", params!(), futures::stream::iter(chunks))
+        .await
+        .unwrap();
+    let sum = (0..64u64).map(|i| i * 64 * 1024).sum::<u64>();
+    assert_eq!(
+        res,
+        ZRpcDt::Array(vec![ZRpcDt::UInt64(64 * 64 * 1024), ZRpcDt::UInt64(sum)])
+    );
+}
+
+#[tokio::test]
+async fn clients_send_no_further_than_their_credit() {
+    let (tx, _) = mpsc::unbounded_channel();
+    let permits = Arc::new(Semaphore::new(0));
+    let client = serve(permits.clone(), tx).await;
+    let pulled = Arc::new(AtomicUsize::new(0));
+
+    let data = futures::stream::iter(0..100).map({
+        let pulled = pulled.clone();
+
+        move |_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+            vec![0u8; 1024]
+        }
+    });
+    let call = tokio::spawn(async move { client.call_upload("slow", params!(), data).await });
+
+    // With the procedure not reading, the client stops at the initial window.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let ahead = pulled.load(Ordering::SeqCst);
+    assert!(ahead > 0 && ahead <= 16, "{} chunks pulled", ahead);
+
+    permits.add_permits(101);
+    let res = timeout(Duration::from_secs(5), call)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(res.unwrap(), ZRpcDt::UInt64(100));
+}
+
+#[tokio::test]
+async fn answers_may_come_before_all_data_is_sent() {
+    let (tx, _) = mpsc::unbounded_channel();
+    let client = serve(Arc::new(Semaphore::new(0)), tx).await;
+
+    // The data never ends, but the procedure is done after the first chunk.
+    let data = futures::stream::repeat(b"hello".to_vec());
+    let res = timeout(
+        Duration::from_secs(5),
+        client.call_upload("peek", params!(), data),
+    )
+    .await
+    .unwrap();
+    assert_eq!(res.unwrap(), ZRpcDt::Bytes(b"hello".to_vec()));
+
+    let res = client
+        .call_upload("nope", params!(), futures::stream::empty::<Vec<u8>>())
+        .await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(name))) if name == "nope"
+        ),
+        "{:?}",
+        res
+    );
+}
+
+#[tokio::test]
+async fn dropping_the_call_aborts_the_upload() {
+    let (tx, mut errors) = mpsc::unbounded_channel();
+    let client = serve(Arc::new(Semaphore::new(0)), tx).await;
+
+    let data = futures::stream::iter([vec![1u8; 16]]).chain(futures::stream::pending());
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call_upload("hang", params!(), data).await })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    call.abort();
+
+    let kind = timeout(Duration::from_secs(5), errors.recv())
+        .await
+        .expect("the upload wasn't aborted")
+        .unwrap();
+    assert_eq!(kind, std::io::ErrorKind::ConnectionAborted);
+    assert!(
+        client
+            .call_upload("peek", params!(), futures::stream::iter([vec![2u8]]))
+            .await
+            .is_ok()
+    );
+}
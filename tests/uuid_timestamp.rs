@@ -0,0 +1,132 @@
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    params,
+    server::ZRpcServer,
+    transport::codec::ZRpcCodec,
+    types::dt::{FromZRpcDt, ZRpcDt},
+};
+
+async fn serve(codec: ZRpcCodec) -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.set_codec(codec);
+    server
+        .add_proc("echo", |params: &Vec<ZRpcDt>| Ok(params[0].clone()))
+        .await;
+    server
+        .add_typed_proc("later", |at: SystemTime| at + Duration::from_secs(60))
+        .await;
+    #[cfg(feature = "uuid")]
+    server
+        .add_typed_proc("nil", |id: uuid::Uuid| id.is_nil())
+        .await;
+    #[cfg(feature = "chrono")]
+    server
+        .add_typed_proc("year", |at: chrono::DateTime<chrono::Utc>| {
+            chrono::Datelike::year(&at)
+        })
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::builder(addr)
+        .codec(codec)
+        .build()
+        .await
+        .unwrap()
+}
+
+#[test]
+fn values_are_encoded_compactly() {
+    let variant = bincode::serialize(&ZRpcDt::Null).unwrap().len();
+
+    assert_eq!(
+        bincode::serialize(&ZRpcDt::Uuid([1; 16])).unwrap().len(),
+        variant + 16
+    );
+    assert_eq!(
+        bincode::serialize(&ZRpcDt::Timestamp(-1)).unwrap().len(),
+        variant + 8
+    );
+}
+
+#[tokio::test]
+async fn values_round_trip_through_every_codec() {
+    for codec in [ZRpcCodec::Bincode, ZRpcCodec::Json] {
+        let client = serve(codec).await;
+
+        for dt in [
+            ZRpcDt::Uuid(*b"\x01\x23\x45\x67\x89\xab\xcd\xef\x01\x23\x45\x67\x89\xab\xcd\xef"),
+            ZRpcDt::Timestamp(i64::MIN),
+            ZRpcDt::Timestamp(1_700_000_000_123_456_789),
+        ] {
+            assert_eq!(client.call("echo", vec![dt.clone()]).await.unwrap(), dt);
+        }
+    }
+}
+
+#[tokio::test]
+async fn system_times_convert_with_nanosecond_precision() {
+    let client = serve(ZRpcCodec::Bincode).await;
+    let at = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+
+    let res = client.call("later", params!(at)).await.unwrap();
+    assert_eq!(res, ZRpcDt::Timestamp(1_700_000_060_123_456_789));
+    assert_eq!(
+        SystemTime::from_zdt(&res).unwrap(),
+        at + Duration::from_secs(60)
+    );
+
+    let before = UNIX_EPOCH - Duration::from_secs(1);
+    assert_eq!(
+        SystemTime::from_zdt(&client.call("echo", params!(before)).await.unwrap()).unwrap(),
+        before
+    );
+}
+
+#[cfg(feature = "uuid")]
+#[tokio::test]
+async fn uuids_are_typed_parameters() {
+    let client = serve(ZRpcCodec::Bincode).await;
+    let id = uuid::Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+
+    assert_eq!(
+        client
+            .call("nil", params!(uuid::Uuid::nil()))
+            .await
+            .unwrap(),
+        ZRpcDt::Bool(true)
+    );
+    assert_eq!(
+        client.call("nil", params!(id)).await.unwrap(),
+        ZRpcDt::Bool(false)
+    );
+    assert_eq!(
+        uuid::Uuid::from_zdt(&client.call("echo", params!(id)).await.unwrap()).unwrap(),
+        id
+    );
+}
+
+#[cfg(feature = "chrono")]
+#[tokio::test]
+async fn chrono_times_are_typed_parameters() {
+    use chrono::{TimeZone, Utc};
+
+    let client = serve(ZRpcCodec::Json).await;
+    let at = Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap();
+
+    assert_eq!(
+        client.call("year", params!(at)).await.unwrap(),
+        ZRpcDt::Int32(2024)
+    );
+    assert_eq!(
+        chrono::DateTime::<Utc>::from_zdt(&client.call("echo", params!(at)).await.unwrap())
+            .unwrap(),
+        at
+    );
+}
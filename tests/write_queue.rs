@@ -0,0 +1,23 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use libzrpc::{client::ZRpcClient, params, server::ZRpcServer, types::dt::ZRpcDt};
+
+#[tokio::test]
+async fn zero_write_queue_limit_still_sends() {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server
+        .add_proc("ping", |_: &Vec<ZRpcDt>| Ok(ZRpcDt::Null))
+        .await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = ZRpcClient::builder(addr)
+        .write_queue_limit(0)
+        .timeout(Duration::from_secs(5))
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(client.call("ping", params!()).await.unwrap(), ZRpcDt::Null);
+}
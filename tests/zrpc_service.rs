@@ -0,0 +1,140 @@
+#![cfg(feature = "derive")]
+
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use libzrpc::{
+    client::ZRpcClient,
+    error::ZRpcError,
+    params,
+    server::{ZRpcServer, service::zrpc_service},
+    types::{ctx::ZRpcContext, dt::ZRpcDt, proc_error::ProcedureError},
+};
+
+struct Counter {
+    total: AtomicI64,
+}
+
+#[zrpc_service]
+impl Counter {
+    fn new() -> Self {
+        Counter {
+            total: AtomicI64::new(0),
+        }
+    }
+
+    async fn add(&self, a: i64, b: i64) -> i64 {
+        self.total.fetch_add(a + b, Ordering::SeqCst) + a + b
+    }
+
+    fn total(&self) -> i64 {
+        self.total.load(Ordering::SeqCst)
+    }
+
+    async fn whoami(&self, ctx: &ZRpcContext) -> Option<String> {
+        ctx.header("user").map(str::to_string)
+    }
+
+    #[zrpc(rename = "zero")]
+    fn reset(&self) {
+        self.total.store(0, Ordering::SeqCst);
+    }
+
+    #[zrpc(skip)]
+    #[allow(dead_code)]
+    fn internal(&self) -> i64 {
+        -1
+    }
+}
+
+async fn serve() -> ZRpcClient {
+    let mut server = ZRpcServer::new((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = server.local_addrs()[0];
+
+    server.add_service("counter", Counter::new()).await;
+    tokio::spawn(async move { server.start().await });
+
+    ZRpcClient::new(addr).await.unwrap()
+}
+
+#[tokio::test]
+async fn methods_share_the_state_in_self() {
+    let client = serve().await;
+
+    assert_eq!(
+        client
+            .call("counter.add", params!(2i64, 3i64))
+            .await
+            .unwrap(),
+        ZRpcDt::Int64(5)
+    );
+    assert_eq!(
+        client
+            .call("counter.add", params!(1i64, 1i64))
+            .await
+            .unwrap(),
+        ZRpcDt::Int64(7)
+    );
+    assert_eq!(
+        client.call("counter.total", params!()).await.unwrap(),
+        ZRpcDt::Int64(7)
+    );
+
+    assert_eq!(
+        client.call("counter.zero", params!()).await.unwrap(),
+        ZRpcDt::Ok
+    );
+    assert_eq!(
+        client.call("counter.total", params!()).await.unwrap(),
+        ZRpcDt::Int64(0)
+    );
+}
+
+#[tokio::test]
+async fn context_parameters_receive_the_call_context() {
+    let client = serve().await;
+
+    let (res, _) = client
+        .call_with_headers(
+            "counter.whoami",
+            params!(),
+            HashMap::from([("user".to_string(), "ada".to_string())]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res, ZRpcDt::String("ada".to_string()));
+    assert_eq!(
+        client.call("counter.whoami", params!()).await.unwrap(),
+        ZRpcDt::Null
+    );
+}
+
+#[tokio::test]
+async fn skipped_renamed_and_badly_called_methods() {
+    let client = serve().await;
+
+    for name in ["counter.internal", "counter.reset", "counter.new"] {
+        let res = client.call(name, params!()).await;
+        assert!(
+            matches!(
+                &res,
+                Err(ZRpcError::Procedure(ProcedureError::MethodNotFound(called))) if called == name
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    let res = client.call("counter.add", params!("two", 3i64)).await;
+    assert!(
+        matches!(
+            &res,
+            Err(ZRpcError::Procedure(ProcedureError::BadParams(_)))
+        ),
+        "{:?}",
+        res
+    );
+}